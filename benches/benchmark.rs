@@ -93,6 +93,32 @@ fn bench_kdtree_nearest_search(c: &mut Criterion) {
                 });
             },
         );
+        group.bench_with_input(
+            BenchmarkId::new("kd_index_tree/coords", log10n),
+            log10n,
+            |b, log10n| {
+                let points = gen_points3d(10usize.pow(*log10n));
+                let kdtree = KdIndexTree::build_by_ordered_float(&points).with_coords();
+                b.iter(|| {
+                    let i = rng.gen::<usize>() % points.len();
+                    assert_eq!(kdtree.nearest(&points[i]).unwrap().item, &i);
+                });
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("kd_bucket_tree", log10n),
+            log10n,
+            |b, log10n| {
+                let kdtree = KdBucketTree::build(gen_points3d(10usize.pow(*log10n)), 16);
+                b.iter(|| {
+                    let i = rng.gen::<usize>() % kdtree.len();
+                    assert_eq!(
+                        kdtree.nearest(&kdtree[i]).unwrap().item.coord,
+                        kdtree[i].coord
+                    );
+                });
+            },
+        );
         group.bench_with_input(
             BenchmarkId::new("kd_tree/nearests", log10n),
             log10n,
@@ -199,6 +225,7 @@ fn bench_kdtree_within_radius(c: &mut Criterion) {
     };
     let kd_tree = KdTree::build_by_ordered_float(points.clone());
     let kd_index_tree = KdIndexTree::build_by_ordered_float(&points);
+    let kd_bucket_tree = KdBucketTree::build(points.clone(), 16);
     for radius in &[0.05, 0.1, 0.2, 0.4] {
         group.bench_with_input(BenchmarkId::new("kd_tree", radius), radius, |b, radius| {
             b.iter(|| {
@@ -216,6 +243,16 @@ fn bench_kdtree_within_radius(c: &mut Criterion) {
                 });
             },
         );
+        group.bench_with_input(
+            BenchmarkId::new("kd_bucket_tree", radius),
+            radius,
+            |b, radius| {
+                b.iter(|| {
+                    let i = rng.gen::<usize>() % N;
+                    let _neighbors = kd_bucket_tree.within_radius(&points[i], *radius);
+                });
+            },
+        );
         group.bench_with_input(BenchmarkId::new("kdtree", radius), radius, |b, radius| {
             b.iter(|| {
                 let i = rng.gen::<usize>() % N;
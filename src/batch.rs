@@ -0,0 +1,452 @@
+use crate::{ItemAndDistance, KdIndexTreeN, KdPoint};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use typenum::Unsigned;
+
+impl_kd_queries! {
+    /// Queries for many input points at once.
+    /// The `par_*` variants run the queries in parallel and require the `rayon` feature.
+    impl {
+        pub fn nearest_batch_by<Q: KdPoint<Dim = N>>(
+            &self,
+            queries: &[Q],
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        ) -> Vec<Option<ItemAndDistance<'_, T, Q::Scalar>>> {
+            queries
+                .iter()
+                .map(|query| self.nearest_by(query, coord))
+                .collect()
+        }
+
+        /// # Example
+        /// ```
+        /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+        /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+        /// let found = kdtree.nearest_batch(&[[3, 1, 2], [1, 2, 2]]);
+        /// assert_eq!(found[0].unwrap().item, &[3, 1, 2]);
+        /// assert_eq!(found[1].unwrap().item, &[1, 2, 3]);
+        /// ```
+        pub fn nearest_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+            &self,
+            queries: &[Q],
+        ) -> Vec<Option<ItemAndDistance<'_, T, T::Scalar>>>
+        where
+            T: KdPoint<Dim = N>,
+        {
+            self.nearest_batch_by(queries, |item, k| item.at(k))
+        }
+
+        pub fn nearests_batch_by<Q: KdPoint<Dim = N>>(
+            &self,
+            queries: &[Q],
+            num: usize,
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        ) -> Vec<Vec<ItemAndDistance<'_, T, Q::Scalar>>> {
+            queries
+                .iter()
+                .map(|query| self.nearests_by(query, num, coord))
+                .collect()
+        }
+
+        pub fn nearests_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+            &self,
+            queries: &[Q],
+            num: usize,
+        ) -> Vec<Vec<ItemAndDistance<'_, T, T::Scalar>>>
+        where
+            T: KdPoint<Dim = N>,
+        {
+            self.nearests_batch_by(queries, num, |item, k| item.at(k))
+        }
+
+        pub fn within_batch_by<Q: KdPoint<Dim = N>>(
+            &self,
+            queries: &[[Q; 2]],
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        ) -> Vec<Vec<&T>> {
+            queries
+                .iter()
+                .map(|query| self.within_by(query, coord))
+                .collect()
+        }
+
+        pub fn within_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+            &self,
+            queries: &[[Q; 2]],
+        ) -> Vec<Vec<&T>>
+        where
+            T: KdPoint<Dim = N>,
+        {
+            self.within_batch_by(queries, |item, k| item.at(k))
+        }
+
+        pub fn within_radius_batch_by<Q: KdPoint<Dim = N>>(
+            &self,
+            queries: &[Q],
+            radius: Q::Scalar,
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        ) -> Vec<Vec<&T>> {
+            queries
+                .iter()
+                .map(|query| self.within_radius_by(query, radius, coord))
+                .collect()
+        }
+
+        pub fn within_radius_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+            &self,
+            queries: &[Q],
+            radius: T::Scalar,
+        ) -> Vec<Vec<&T>>
+        where
+            T: KdPoint<Dim = N>,
+        {
+            self.within_radius_batch_by(queries, radius, |item, k| item.at(k))
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_nearest_batch_by<Q>(
+            &self,
+            queries: &[Q],
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+        ) -> Vec<Option<ItemAndDistance<'_, T, Q::Scalar>>>
+        where
+            N: Sync,
+            T: Sync,
+            Q: KdPoint<Dim = N> + Sync,
+            Q::Scalar: Send,
+        {
+            queries
+                .par_iter()
+                .map(|query| self.nearest_by(query, coord))
+                .collect()
+        }
+
+        /// # Example
+        /// ```
+        /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+        /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+        /// let found = kdtree.par_nearest_batch(&[[3, 1, 2], [1, 2, 2]]);
+        /// assert_eq!(found[0].unwrap().item, &[3, 1, 2]);
+        /// assert_eq!(found[1].unwrap().item, &[1, 2, 3]);
+        /// ```
+        #[cfg(feature = "rayon")]
+        pub fn par_nearest_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+            &self,
+            queries: &[Q],
+        ) -> Vec<Option<ItemAndDistance<'_, T, T::Scalar>>>
+        where
+            N: Sync,
+            T: KdPoint<Dim = N> + Sync,
+            T::Scalar: Send,
+        {
+            self.par_nearest_batch_by(queries, |item, k| item.at(k))
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_nearests_batch_by<Q>(
+            &self,
+            queries: &[Q],
+            num: usize,
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+        ) -> Vec<Vec<ItemAndDistance<'_, T, Q::Scalar>>>
+        where
+            N: Sync,
+            T: Sync,
+            Q: KdPoint<Dim = N> + Sync,
+            Q::Scalar: Send,
+        {
+            queries
+                .par_iter()
+                .map(|query| self.nearests_by(query, num, coord))
+                .collect()
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_nearests_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+            &self,
+            queries: &[Q],
+            num: usize,
+        ) -> Vec<Vec<ItemAndDistance<'_, T, T::Scalar>>>
+        where
+            N: Sync,
+            T: KdPoint<Dim = N> + Sync,
+            T::Scalar: Send,
+        {
+            self.par_nearests_batch_by(queries, num, |item, k| item.at(k))
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_within_batch_by<Q>(
+            &self,
+            queries: &[[Q; 2]],
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+        ) -> Vec<Vec<&T>>
+        where
+            N: Sync,
+            T: Sync,
+            Q: KdPoint<Dim = N> + Sync,
+        {
+            queries
+                .par_iter()
+                .map(|query| self.within_by(query, coord))
+                .collect()
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_within_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+            &self,
+            queries: &[[Q; 2]],
+        ) -> Vec<Vec<&T>>
+        where
+            N: Sync,
+            T: KdPoint<Dim = N> + Sync,
+        {
+            self.par_within_batch_by(queries, |item, k| item.at(k))
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_within_radius_batch_by<Q>(
+            &self,
+            queries: &[Q],
+            radius: Q::Scalar,
+            coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+        ) -> Vec<Vec<&T>>
+        where
+            N: Sync,
+            T: Sync,
+            Q: KdPoint<Dim = N> + Sync,
+            Q::Scalar: Send + Sync,
+        {
+            queries
+                .par_iter()
+                .map(|query| self.within_radius_by(query, radius, coord))
+                .collect()
+        }
+
+        #[cfg(feature = "rayon")]
+        pub fn par_within_radius_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+            &self,
+            queries: &[Q],
+            radius: T::Scalar,
+        ) -> Vec<Vec<&T>>
+        where
+            N: Sync,
+            T: KdPoint<Dim = N> + Sync,
+            T::Scalar: Send + Sync,
+        {
+            self.par_within_radius_batch_by(queries, radius, |item, k| item.at(k))
+        }
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    pub fn nearest_batch_by<Q: KdPoint<Dim = N>>(
+        &self,
+        queries: &[Q],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<Option<ItemAndDistance<'_, usize, Q::Scalar>>> {
+        self.kdtree
+            .nearest_batch_by(queries, |&index, k| coord(&self.source[index], k))
+    }
+
+    /// # Example
+    /// ```
+    /// let items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// let found = kdtree.nearest_batch(&[[3, 1, 2], [1, 2, 2]]);
+    /// assert_eq!(found[0].unwrap().item, &1);
+    /// assert_eq!(found[1].unwrap().item, &0);
+    /// ```
+    pub fn nearest_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        queries: &[Q],
+    ) -> Vec<Option<ItemAndDistance<'_, usize, T::Scalar>>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_batch_by(queries, |item, k| item.at(k))
+    }
+
+    pub fn nearests_batch_by<Q: KdPoint<Dim = N>>(
+        &self,
+        queries: &[Q],
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<Vec<ItemAndDistance<'_, usize, Q::Scalar>>> {
+        self.kdtree
+            .nearests_batch_by(queries, num, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn nearests_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        queries: &[Q],
+        num: usize,
+    ) -> Vec<Vec<ItemAndDistance<'_, usize, T::Scalar>>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_batch_by(queries, num, |item, k| item.at(k))
+    }
+
+    pub fn within_batch_by<Q: KdPoint<Dim = N>>(
+        &self,
+        queries: &[[Q; 2]],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<Vec<&usize>> {
+        self.kdtree
+            .within_batch_by(queries, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn within_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        queries: &[[Q; 2]],
+    ) -> Vec<Vec<&usize>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_batch_by(queries, |item, k| item.at(k))
+    }
+
+    pub fn within_radius_batch_by<Q: KdPoint<Dim = N>>(
+        &self,
+        queries: &[Q],
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<Vec<&usize>> {
+        self.kdtree
+            .within_radius_batch_by(queries, radius, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn within_radius_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        queries: &[Q],
+        radius: T::Scalar,
+    ) -> Vec<Vec<&usize>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_batch_by(queries, radius, |item, k| item.at(k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_nearest_batch_by<Q>(
+        &self,
+        queries: &[Q],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+    ) -> Vec<Option<ItemAndDistance<'_, usize, Q::Scalar>>>
+    where
+        N: Sync,
+        T: Sync,
+        Q: KdPoint<Dim = N> + Sync,
+        Q::Scalar: Send,
+    {
+        let source = self.source;
+        self.kdtree
+            .par_nearest_batch_by(queries, move |&index, k| coord(&source[index], k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_nearest_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+        &self,
+        queries: &[Q],
+    ) -> Vec<Option<ItemAndDistance<'_, usize, T::Scalar>>>
+    where
+        N: Sync,
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_nearest_batch_by(queries, |item, k| item.at(k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_nearests_batch_by<Q>(
+        &self,
+        queries: &[Q],
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+    ) -> Vec<Vec<ItemAndDistance<'_, usize, Q::Scalar>>>
+    where
+        N: Sync,
+        T: Sync,
+        Q: KdPoint<Dim = N> + Sync,
+        Q::Scalar: Send,
+    {
+        let source = self.source;
+        self.kdtree
+            .par_nearests_batch_by(queries, num, move |&index, k| coord(&source[index], k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_nearests_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+        &self,
+        queries: &[Q],
+        num: usize,
+    ) -> Vec<Vec<ItemAndDistance<'_, usize, T::Scalar>>>
+    where
+        N: Sync,
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_nearests_batch_by(queries, num, |item, k| item.at(k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_within_batch_by<Q>(
+        &self,
+        queries: &[[Q; 2]],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+    ) -> Vec<Vec<&usize>>
+    where
+        N: Sync,
+        T: Sync,
+        Q: KdPoint<Dim = N> + Sync,
+    {
+        let source = self.source;
+        self.kdtree
+            .par_within_batch_by(queries, move |&index, k| coord(&source[index], k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_within_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+        &self,
+        queries: &[[Q; 2]],
+    ) -> Vec<Vec<&usize>>
+    where
+        N: Sync,
+        T: KdPoint<Dim = N> + Sync,
+    {
+        self.par_within_batch_by(queries, |item, k| item.at(k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_within_radius_batch_by<Q>(
+        &self,
+        queries: &[Q],
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy + Send + Sync,
+    ) -> Vec<Vec<&usize>>
+    where
+        N: Sync,
+        T: Sync,
+        Q: KdPoint<Dim = N> + Sync,
+        Q::Scalar: Send + Sync,
+    {
+        let source = self.source;
+        self.kdtree
+            .par_within_radius_batch_by(queries, radius, move |&index, k| coord(&source[index], k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_within_radius_batch<Q: KdPoint<Scalar = T::Scalar, Dim = N> + Sync>(
+        &self,
+        queries: &[Q],
+        radius: T::Scalar,
+    ) -> Vec<Vec<&usize>>
+    where
+        N: Sync,
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send + Sync,
+    {
+        self.par_within_radius_batch_by(queries, radius, |item, k| item.at(k))
+    }
+}
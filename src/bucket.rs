@@ -0,0 +1,559 @@
+use crate::nearests::{sort_by_distance, NearestHeap};
+use crate::{ItemAndDistance, KdPoint};
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use typenum::Unsigned;
+
+/// A kd-tree with explicit nodes, which stores items in leaf buckets and keeps a tight bounding box for each node.
+///
+/// Unlike [`KdTreeN`](crate::KdTreeN), whose nodes are single items split at the median,
+/// a node of this tree is split along the axis of the largest spread until it contains at most `bucket_size` items.
+/// Queries prune nodes by the distance to their bounding boxes instead of the distance to the split planes,
+/// and range queries take all the items of a node at once if its bounding box is inside the range.
+/// # Example
+/// ```
+/// let kdtree = kd_tree::KdBucketTree::build(vec![[1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]], 16);
+/// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+/// assert_eq!(kdtree.within_radius(&[2.0, 1.5, 2.5], 1.5).len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdBucketTreeN<T, N: Unsigned, Scalar> {
+    items: Vec<T>,
+    nodes: Vec<Node<Scalar>>,
+    /// `[min[0], .., min[dim - 1], max[0], .., max[dim - 1]]` of each node.
+    bounds: Vec<Scalar>,
+    bucket_size: usize,
+    _dim: PhantomData<N>,
+}
+pub type KdBucketTree<T> = KdBucketTreeN<T, <T as KdPoint>::Dim, <T as KdPoint>::Scalar>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node<Scalar> {
+    start: usize,
+    end: usize,
+    split: Option<Split<Scalar>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Split<Scalar> {
+    axis: usize,
+    value: Scalar,
+    left: usize,
+    right: usize,
+}
+
+/// How to split the nodes of [`KdBucketTreeN`], or of [`KdTreeN`](crate::KdTreeN) and [`KdIndexTreeN`](crate::KdIndexTreeN)
+/// built by `build_with_strategy*`.
+///
+/// The axis chosen for each node is stored in the tree, so that the queries don't depend on the depth.
+/// [`KdSliceN`](crate::KdSliceN) has nowhere to store the axes, so a sorted slice always uses [`SplitStrategy::RoundRobin`].
+/// Since [`KdTreeN`](crate::KdTreeN) splits every node at the median,
+/// [`SplitStrategy::SlidingMidpoint`] is only supported by [`KdBucketTreeN`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SplitStrategy {
+    /// Cycles the axis by the depth of the node, and splits at the median.
+    RoundRobin,
+    /// Splits at the median along the axis of the largest spread. This is the default.
+    #[default]
+    MaxSpread,
+    /// Splits at the middle of the bounding box along the axis of the largest spread.
+    /// If all the items are on one side, the split slides to the nearest item, so that no node is empty.
+    /// This keeps the nodes from becoming thin slabs on highly anisotropic data.
+    SlidingMidpoint,
+}
+
+struct Builder<Scalar, F> {
+    nodes: Vec<Node<Scalar>>,
+    bounds: Vec<Scalar>,
+    dim: usize,
+    bucket_size: usize,
+    strategy: SplitStrategy,
+    coord: F,
+}
+
+impl<Scalar, F> Builder<Scalar, F>
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    /// Builds the subtree of `items`, which start at `start` in the whole items, and returns the id of its root.
+    fn build<T>(&mut self, items: &mut [T], start: usize, depth: usize) -> usize
+    where
+        F: Fn(&T, usize) -> Scalar + Copy,
+    {
+        let (dim, coord) = (self.dim, self.coord);
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            start,
+            end: start + items.len(),
+            split: None,
+        });
+        let offset = self.bounds.len();
+        for _ in 0..2 {
+            for k in 0..dim {
+                self.bounds.push(coord(&items[0], k));
+            }
+        }
+        let bounds = &mut self.bounds[offset..];
+        for item in items.iter() {
+            for k in 0..dim {
+                let x = coord(item, k);
+                if x < bounds[k] {
+                    bounds[k] = x;
+                }
+                if x > bounds[dim + k] {
+                    bounds[dim + k] = x;
+                }
+            }
+        }
+        if items.len() <= self.bucket_size {
+            return id;
+        }
+        let max_spread_axis = || {
+            (0..dim)
+                .map(|k| (k, bounds[dim + k] - bounds[k]))
+                .fold((0, Scalar::zero()), |max, (k, spread)| {
+                    if spread > max.1 {
+                        (k, spread)
+                    } else {
+                        max
+                    }
+                })
+                .0
+        };
+        let compare = |axis: usize| {
+            move |item1: &T, item2: &T| {
+                coord(item1, axis)
+                    .partial_cmp(&coord(item2, axis))
+                    .unwrap_or(Ordering::Equal)
+            }
+        };
+        let axis = match self.strategy {
+            SplitStrategy::RoundRobin => depth % dim,
+            SplitStrategy::MaxSpread | SplitStrategy::SlidingMidpoint => max_spread_axis(),
+        };
+        let (min, max) = (bounds[axis], bounds[dim + axis]);
+        let (mid, value) = if self.strategy == SplitStrategy::SlidingMidpoint && min < max {
+            let value = min + (max - min) / (Scalar::one() + Scalar::one());
+            let mut mid = 0;
+            for i in 0..items.len() {
+                if coord(&items[i], axis) < value {
+                    items.swap(i, mid);
+                    mid += 1;
+                }
+            }
+            if mid == 0 {
+                // all the items are on the upper side; the lowest one goes to the lower side.
+                pdqselect::select_by(items, 0, compare(axis));
+                mid = 1;
+            } else if mid == items.len() {
+                // all the items are on the lower side; the highest one goes to the upper side.
+                mid -= 1;
+                pdqselect::select_by(items, mid, compare(axis));
+            }
+            (mid, value)
+        } else {
+            // the median split is also used when all the items are at the same point.
+            let mid = items.len() / 2;
+            pdqselect::select_by(items, mid, compare(axis));
+            (mid, coord(&items[mid], axis))
+        };
+        let (lower, upper) = items.split_at_mut(mid);
+        let left = self.build(lower, start, depth + 1);
+        let right = self.build(upper, start + mid, depth + 1);
+        self.nodes[id].split = Some(Split {
+            axis,
+            value,
+            left,
+            right,
+        });
+        id
+    }
+}
+
+impl<T, N: Unsigned, Scalar> std::ops::Deref for KdBucketTreeN<T, N, Scalar> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T, N: Unsigned, Scalar> From<KdBucketTreeN<T, N, Scalar>> for Vec<T> {
+    fn from(src: KdBucketTreeN<T, N, Scalar>) -> Self {
+        src.items
+    }
+}
+
+impl<T, N: Unsigned, Scalar> KdBucketTreeN<T, N, Scalar>
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    /// Returns the items, which are ordered so that the items of each node are contiguous.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// # Panics
+    /// Panics if `bucket_size` is zero.
+    /// # Example
+    /// ```
+    /// struct Item {
+    ///     point: [f64; 3],
+    ///     id: usize,
+    /// }
+    /// let kdtree = kd_tree::KdBucketTreeN::<_, kd_tree::Const<3>, _>::build_by(
+    ///     vec![
+    ///         Item { point: [1.0, 2.0, 3.0], id: 111 },
+    ///         Item { point: [3.0, 1.0, 2.0], id: 222 },
+    ///         Item { point: [2.0, 3.0, 1.0], id: 333 },
+    ///     ],
+    ///     8,
+    ///     |item, k| item.point[k],
+    /// );
+    /// assert_eq!(kdtree.nearest_by(&[3.1, 0.9, 2.1], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// ```
+    pub fn build_by(
+        items: Vec<T>,
+        bucket_size: usize,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Self {
+        Self::build_with_strategy_by(items, bucket_size, SplitStrategy::default(), coord)
+    }
+
+    /// # Panics
+    /// Panics if `bucket_size` is zero.
+    pub fn build(points: Vec<T>, bucket_size: usize) -> Self
+    where
+        T: KdPoint<Dim = N, Scalar = Scalar>,
+    {
+        Self::build_by(points, bucket_size, |item, k| item.at(k))
+    }
+
+    /// Same as [`KdBucketTreeN::build_by`], but splits the nodes according to `strategy`.
+    /// # Panics
+    /// Panics if `bucket_size` is zero.
+    pub fn build_with_strategy_by(
+        mut items: Vec<T>,
+        bucket_size: usize,
+        strategy: SplitStrategy,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Self {
+        assert!(bucket_size > 0, "bucket size must be positive");
+        let mut builder = Builder {
+            nodes: Vec::new(),
+            bounds: Vec::new(),
+            dim: N::to_usize(),
+            bucket_size,
+            strategy,
+            coord,
+        };
+        if !items.is_empty() {
+            builder.build(&mut items, 0, 0);
+        }
+        Self {
+            items,
+            nodes: builder.nodes,
+            bounds: builder.bounds,
+            bucket_size,
+            _dim: PhantomData,
+        }
+    }
+
+    /// Same as [`KdBucketTreeN::build`], but splits the nodes according to `strategy`.
+    /// # Panics
+    /// Panics if `bucket_size` is zero.
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdBucketTree, SplitStrategy};
+    /// let points = vec![[1.0, 2.0, 0.0], [3.0, 1.0, 0.1], [2.0, 3.0, 0.0], [4.0, 4.0, 0.1]];
+    /// let kdtree = KdBucketTree::build_with_strategy(points, 1, SplitStrategy::SlidingMidpoint);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 0.0]).unwrap().item, &[3.0, 1.0, 0.1]);
+    /// ```
+    pub fn build_with_strategy(points: Vec<T>, bucket_size: usize, strategy: SplitStrategy) -> Self
+    where
+        T: KdPoint<Dim = N, Scalar = Scalar>,
+    {
+        Self::build_with_strategy_by(points, bucket_size, strategy, |item, k| item.at(k))
+    }
+
+    /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
+    pub fn nearest_by<Q: KdPoint<Scalar = Scalar, Dim = N>>(
+        &self,
+        query: &Q,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearests_by(query, 1, coord).pop()
+    }
+
+    /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
+    pub fn nearest(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>>
+    where
+        T: KdPoint<Scalar = Scalar, Dim = N>,
+    {
+        self.nearest_by(query, |item, k| item.at(k))
+    }
+
+    /// Returns `num` nearest items sorted by the distance from the input point.
+    pub fn nearests_by<Q: KdPoint<Scalar = Scalar, Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let mut nearests = self.nearests_unsorted_by(query, num, coord);
+        sort_by_distance(&mut nearests);
+        nearests
+    }
+
+    /// Same as [`KdBucketTreeN::nearests_by`], but the result is in arbitrary order.
+    pub fn nearests_unsorted_by<Q: KdPoint<Scalar = Scalar, Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let mut nearests = NearestHeap::new(num);
+        if num > 0 && !self.is_empty() {
+            self.nearests_recurse(&mut nearests, 0, query, coord);
+        }
+        nearests.into_vec()
+    }
+
+    /// Returns `num` nearest items sorted by the distance from the input point.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdBucketTree::build(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]], 2);
+    /// let found = kdtree.nearests(&[3, 1, 2], 2);
+    /// assert_eq!(found[0].item, &[3, 1, 2]);
+    /// assert_eq!(found[1].item, &[2, 3, 1]);
+    /// ```
+    pub fn nearests(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>>
+    where
+        T: KdPoint<Scalar = Scalar, Dim = N>,
+    {
+        self.nearests_by(query, num, |item, k| item.at(k))
+    }
+
+    /// Same as [`KdBucketTreeN::nearests`], but the result is in arbitrary order.
+    pub fn nearests_unsorted(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>>
+    where
+        T: KdPoint<Scalar = Scalar, Dim = N>,
+    {
+        self.nearests_unsorted_by(query, num, |item, k| item.at(k))
+    }
+
+    fn nearests_recurse<'a, Q: KdPoint<Scalar = Scalar>>(
+        &'a self,
+        nearests: &mut NearestHeap<'a, T, Scalar>,
+        id: usize,
+        query: &Q,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) {
+        let node = &self.nodes[id];
+        match &node.split {
+            None => {
+                for item in &self.items[node.start..node.end] {
+                    let squared_distance = squared_distance(query, item, coord);
+                    if nearests.admits(squared_distance, None) {
+                        nearests.push(item, squared_distance);
+                    }
+                }
+            }
+            Some(split) => {
+                let [near, far] = if query.at(split.axis) < split.value {
+                    [split.left, split.right]
+                } else {
+                    [split.right, split.left]
+                };
+                for child in [near, far] {
+                    if nearests.admits(self.min_squared_distance(child, query), None) {
+                        self.nearests_recurse(nearests, child, query, coord);
+                    }
+                }
+            }
+        }
+    }
+
+    /// search items within a rectangular region.
+    pub fn within_by<Q: KdPoint<Scalar = Scalar, Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<&T> {
+        assert!((0..Q::dim()).all(|k| query[0].at(k) <= query[1].at(k)));
+        let dim = Q::dim();
+        let mut results = Vec::new();
+        self.within_recurse(
+            &mut results,
+            |id| {
+                let (min, max) = self.bounds(id);
+                if (0..dim).any(|k| max[k] < query[0].at(k) || query[1].at(k) < min[k]) {
+                    Some(false)
+                } else if (0..dim).all(|k| query[0].at(k) <= min[k] && max[k] <= query[1].at(k)) {
+                    Some(true)
+                } else {
+                    None
+                }
+            },
+            |item| {
+                (0..dim)
+                    .all(|k| query[0].at(k) <= coord(item, k) && coord(item, k) <= query[1].at(k))
+            },
+        );
+        results
+    }
+
+    /// search items within a rectangular region.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdBucketTree::build(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]], 2);
+    /// assert_eq!(kdtree.within(&[[1, 1, 1], [2, 3, 3]]).len(), 2);
+    /// ```
+    pub fn within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> Vec<&T>
+    where
+        T: KdPoint<Scalar = Scalar, Dim = N>,
+    {
+        self.within_by(query, |item, k| item.at(k))
+    }
+
+    /// search items within a sphere.
+    pub fn within_radius_by<Q: KdPoint<Scalar = Scalar, Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Scalar,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<&T> {
+        let squared_radius = radius * radius;
+        let mut results = Vec::new();
+        self.within_recurse(
+            &mut results,
+            |id| {
+                if self.min_squared_distance(id, query) >= squared_radius {
+                    Some(false)
+                } else if self.max_squared_distance(id, query) < squared_radius {
+                    Some(true)
+                } else {
+                    None
+                }
+            },
+            |item| squared_distance(query, item, coord) < squared_radius,
+        );
+        results
+    }
+
+    /// search items within a sphere.
+    pub fn within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Scalar = Scalar, Dim = N>,
+    {
+        self.within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    /// `classify` returns `Some(true)` if all the items of the node are inside,
+    /// `Some(false)` if none of them are inside, or `None` if unknown.
+    fn within_recurse<'a>(
+        &'a self,
+        results: &mut Vec<&'a T>,
+        classify: impl Fn(usize) -> Option<bool> + Copy,
+        contains: impl Fn(&T) -> bool + Copy,
+    ) {
+        fn recurse<'a, T, Scalar>(
+            tree_nodes: &[Node<Scalar>],
+            items: &'a [T],
+            results: &mut Vec<&'a T>,
+            id: usize,
+            classify: impl Fn(usize) -> Option<bool> + Copy,
+            contains: impl Fn(&T) -> bool + Copy,
+        ) {
+            let node = &tree_nodes[id];
+            match classify(id) {
+                Some(false) => {}
+                Some(true) => results.extend(&items[node.start..node.end]),
+                None => match &node.split {
+                    None => results.extend(
+                        items[node.start..node.end]
+                            .iter()
+                            .filter(|item| contains(item)),
+                    ),
+                    Some(split) => {
+                        recurse(tree_nodes, items, results, split.left, classify, contains);
+                        recurse(tree_nodes, items, results, split.right, classify, contains);
+                    }
+                },
+            }
+        }
+        if !self.is_empty() {
+            recurse(&self.nodes, &self.items, results, 0, classify, contains);
+        }
+    }
+
+    fn bounds(&self, id: usize) -> (&[Scalar], &[Scalar]) {
+        let dim = N::to_usize();
+        self.bounds[2 * dim * id..2 * dim * (id + 1)].split_at(dim)
+    }
+
+    /// The squared distance from `query` to the bounding box of the node.
+    fn min_squared_distance(&self, id: usize, query: &impl KdPoint<Scalar = Scalar>) -> Scalar {
+        let (min, max) = self.bounds(id);
+        let mut squared_distance = Scalar::zero();
+        for k in 0..min.len() {
+            let x = query.at(k);
+            let diff = if x < min[k] {
+                min[k] - x
+            } else if max[k] < x {
+                x - max[k]
+            } else {
+                continue;
+            };
+            squared_distance += diff * diff;
+        }
+        squared_distance
+    }
+
+    /// The squared distance from `query` to the farthest corner of the bounding box of the node.
+    fn max_squared_distance(&self, id: usize, query: &impl KdPoint<Scalar = Scalar>) -> Scalar {
+        let (min, max) = self.bounds(id);
+        let mut squared_distance = Scalar::zero();
+        for k in 0..min.len() {
+            let x = query.at(k);
+            let diff = if x - min[k] > max[k] - x {
+                x - min[k]
+            } else {
+                max[k] - x
+            };
+            squared_distance += diff * diff;
+        }
+        squared_distance
+    }
+}
+
+fn squared_distance<T, Q: KdPoint>(
+    query: &Q,
+    item: &T,
+    coord: impl Fn(&T, usize) -> Q::Scalar,
+) -> Q::Scalar {
+    let mut squared_distance = <Q::Scalar as num_traits::Zero>::zero();
+    for k in 0..Q::dim() {
+        let diff = query.at(k) - coord(item, k);
+        squared_distance += diff * diff;
+    }
+    squared_distance
+}
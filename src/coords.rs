@@ -0,0 +1,896 @@
+use crate::nearest::kd_nearest_approx_by;
+use crate::nearests::{kd_nearests_approx_into_by, sort_by_distance};
+use crate::within::kd_for_each_within_by_cmp;
+use crate::within::{kd_any_within_by_cmp, kd_count_within_by_cmp};
+use crate::{
+    approx_factor, radius_compare, ItemAndDistance, KdIndexTreeN, KdPoint, KdTreeN, Metric,
+    SquaredEuclidean, WithinRadiusOptions,
+};
+use std::cmp::Ordering;
+use typenum::Unsigned;
+
+/// Reads the `k`-th coordinate of `item`, which is at `position` in the kd-tree, for the query kernels.
+///
+/// The `coord` closures of the `*_by` queries ignore the position,
+/// while [`Cached`] ignores the item and reads the coordinate cache at the position.
+pub trait CoordAt<T, S>: Copy {
+    fn at(&self, position: usize, item: &T, k: usize) -> S;
+}
+
+impl<T, S, F: Fn(&T, usize) -> S + Copy> CoordAt<T, S> for F {
+    fn at(&self, _position: usize, item: &T, k: usize) -> S {
+        self(item, k)
+    }
+}
+
+/// Reads the coordinates from a cache of `dim` coordinates per item, and maps them by `f`.
+#[derive(Clone, Copy)]
+pub struct Cached<'a, S, F> {
+    coords: &'a [S],
+    dim: usize,
+    f: F,
+}
+
+impl<T, S: Copy, R, F: Fn(S, usize) -> R + Copy> CoordAt<T, R> for Cached<'_, S, F> {
+    fn at(&self, position: usize, _item: &T, k: usize) -> R {
+        (self.f)(self.coords[position * self.dim + k], k)
+    }
+}
+
+/// A kd-tree which keeps a contiguous copy of the coordinates of its items, in the kd-tree order.
+///
+/// The queries read the coordinates of each node from the copy by the position of the node,
+/// instead of calling `coord` on the items. This avoids touching large items such as the values of [`KdMap`](crate::KdMap).
+/// This type implements [`std::ops::Deref`] to `[T]`.
+/// It is returned by [`KdTreeN::with_coords`] and [`KdTreeN::with_coords_by`].
+/// # Example
+/// ```
+/// let kdmap: kd_tree::KdMap<[i32; 3], String> = kd_tree::KdMap::build(vec![
+///     ([1, 2, 3], "foo".to_string()),
+///     ([3, 1, 2], "bar".to_string()),
+/// ]);
+/// let kdtree = kdmap.with_coords();
+/// let found = kdtree.nearest(&[3, 1, 1]).unwrap();
+/// assert_eq!(found.item.1, "bar");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdCoordsTreeN<T, Scalar, N: Unsigned> {
+    kdtree: KdTreeN<T, N>,
+    coords: Vec<Scalar>,
+}
+pub type KdCoordsTree<T> = KdCoordsTreeN<T, <T as KdPoint>::Scalar, <T as KdPoint>::Dim>;
+
+impl<T, Scalar, N: Unsigned> std::ops::Deref for KdCoordsTreeN<T, Scalar, N> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.kdtree
+    }
+}
+
+impl<T, N: Unsigned> KdTreeN<T, N> {
+    /// Returns the kd-tree keeping a contiguous copy of the coordinates given by `coord`.
+    pub fn with_coords_by<Scalar: Copy>(
+        self,
+        coord: impl Fn(&T, usize) -> Scalar,
+    ) -> KdCoordsTreeN<T, Scalar, N> {
+        let dim = N::to_usize();
+        let mut coords = Vec::with_capacity(self.len() * dim);
+        for item in self.iter() {
+            coords.extend((0..dim).map(|k| coord(item, k)));
+        }
+        KdCoordsTreeN {
+            kdtree: self,
+            coords,
+        }
+    }
+
+    /// Returns the kd-tree keeping a contiguous copy of the coordinates of the points.
+    pub fn with_coords(self) -> KdCoordsTreeN<T, T::Scalar, N>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.with_coords_by(|item, k| item.at(k))
+    }
+}
+
+impl<T, Scalar, N: Unsigned> KdCoordsTreeN<T, Scalar, N>
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    pub fn items(&self) -> &[T] {
+        &self.kdtree
+    }
+
+    /// Returns the coordinates of the `i`-th item.
+    pub fn row(&self, i: usize) -> &[Scalar] {
+        let dim = N::to_usize();
+        &self.coords[i * dim..(i + 1) * dim]
+    }
+
+    /// Drops the copy of the coordinates.
+    pub fn into_inner(self) -> KdTreeN<T, N> {
+        self.kdtree
+    }
+
+    fn cached<R, F: Fn(Scalar, usize) -> R + Copy>(&self, f: F) -> Cached<'_, Scalar, F> {
+        Cached {
+            coords: &self.coords,
+            dim: N::to_usize(),
+            f,
+        }
+    }
+
+    /// Returns the distance from the input point to the item at `position`, read from the copy.
+    fn distance<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        metric: &M,
+        position: usize,
+    ) -> Scalar {
+        let row = self.row(position);
+        metric.distance(N::to_usize(), |k| query.at(k) - row[k])
+    }
+
+    fn nearest_approx_with<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        metric: &M,
+        factor: Scalar,
+        max_visits: usize,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(kd_nearest_approx_by(
+                &self.kdtree,
+                |k| query.at(k),
+                N::to_usize(),
+                self.kdtree.split_axes(),
+                metric,
+                factor,
+                max_visits,
+                self.cached(|a, _| a),
+            ))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn nearests_unsorted_into_with<'a, M: Metric<Scalar>>(
+        &'a self,
+        nearests: &mut Vec<ItemAndDistance<'a, T, Scalar>>,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+        factor: Scalar,
+        max_visits: usize,
+        max_distance: Option<Scalar>,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) {
+        kd_nearests_approx_into_by(
+            nearests,
+            &self.kdtree,
+            |k| query.at(k),
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            num,
+            metric,
+            factor,
+            max_visits,
+            max_distance,
+            predicate,
+            self.cached(|a, _| a),
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn nearests_with<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+        factor: Scalar,
+        max_visits: usize,
+        max_distance: Option<Scalar>,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let mut nearests = Vec::new();
+        self.nearests_unsorted_into_with(
+            &mut nearests,
+            query,
+            num,
+            metric,
+            factor,
+            max_visits,
+            max_distance,
+            predicate,
+        );
+        sort_by_distance(&mut nearests);
+        nearests
+    }
+
+    /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
+    pub fn nearest(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearest_approx_with(query, &SquaredEuclidean, Scalar::one(), usize::MAX)
+    }
+
+    /// Same as [`KdSliceN::nearest_with_metric`](crate::KdSliceN::nearest_with_metric).
+    pub fn nearest_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        metric: &M,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearest_approx_with(query, metric, Scalar::one(), usize::MAX)
+    }
+
+    /// Same as [`KdSliceN::nearest_filtered`](crate::KdSliceN::nearest_filtered).
+    pub fn nearest_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearests_filtered(query, 1, predicate).pop()
+    }
+
+    /// Same as [`KdSliceN::nearest_approx`](crate::KdSliceN::nearest_approx).
+    pub fn nearest_approx(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        eps: Scalar,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearest_approx_with(query, &SquaredEuclidean, approx_factor(eps), usize::MAX)
+    }
+
+    /// Same as [`KdSliceN::nearest_with_budget`](crate::KdSliceN::nearest_with_budget).
+    pub fn nearest_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        max_visits: usize,
+    ) -> Option<ItemAndDistance<'_, T, Scalar>> {
+        self.nearest_approx_with(query, &SquaredEuclidean, Scalar::one(), max_visits)
+    }
+
+    /// Returns `num` nearest items sorted by the distance from the input point.
+    pub fn nearests(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let one = Scalar::one();
+        self.nearests_with(query, num, &SquaredEuclidean, one, usize::MAX, None, |_| {
+            true
+        })
+    }
+
+    /// Same as [`KdSliceN::nearests_unsorted`](crate::KdSliceN::nearests_unsorted).
+    pub fn nearests_unsorted(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let mut nearests = Vec::new();
+        self.nearests_unsorted_into_with(
+            &mut nearests,
+            query,
+            num,
+            &SquaredEuclidean,
+            Scalar::one(),
+            usize::MAX,
+            None,
+            |_| true,
+        );
+        nearests
+    }
+
+    /// Same as [`KdSliceN::nearests_into`](crate::KdSliceN::nearests_into).
+    pub fn nearests_into<'a>(
+        &'a self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'a, T, Scalar>>,
+    ) {
+        self.nearests_unsorted_into_with(
+            nearests,
+            query,
+            num,
+            &SquaredEuclidean,
+            Scalar::one(),
+            usize::MAX,
+            None,
+            |_| true,
+        );
+        sort_by_distance(nearests);
+    }
+
+    /// Same as [`KdSliceN::nearests_with_metric`](crate::KdSliceN::nearests_with_metric).
+    pub fn nearests_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        self.nearests_with(query, num, metric, Scalar::one(), usize::MAX, None, |_| {
+            true
+        })
+    }
+
+    /// Same as [`KdSliceN::nearests_within_radius`](crate::KdSliceN::nearests_within_radius).
+    pub fn nearests_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        max_radius: Scalar,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let one = Scalar::one();
+        let max_distance = Some(max_radius * max_radius);
+        self.nearests_with(
+            query,
+            num,
+            &SquaredEuclidean,
+            one,
+            usize::MAX,
+            max_distance,
+            |_| true,
+        )
+    }
+
+    /// Same as [`KdSliceN::nearests_filtered`](crate::KdSliceN::nearests_filtered).
+    pub fn nearests_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let one = Scalar::one();
+        self.nearests_with(
+            query,
+            num,
+            &SquaredEuclidean,
+            one,
+            usize::MAX,
+            None,
+            predicate,
+        )
+    }
+
+    /// Same as [`KdSliceN::nearests_approx`](crate::KdSliceN::nearests_approx).
+    pub fn nearests_approx(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        eps: Scalar,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let factor = approx_factor(eps);
+        self.nearests_with(
+            query,
+            num,
+            &SquaredEuclidean,
+            factor,
+            usize::MAX,
+            None,
+            |_| true,
+        )
+    }
+
+    /// Same as [`KdSliceN::nearests_with_budget`](crate::KdSliceN::nearests_with_budget).
+    pub fn nearests_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        max_visits: usize,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let one = Scalar::one();
+        self.nearests_with(query, num, &SquaredEuclidean, one, max_visits, None, |_| {
+            true
+        })
+    }
+
+    /// search items within a rectangular region.
+    pub fn within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> Vec<&T> {
+        let mut results = Vec::new();
+        self.within_into(query, &mut results);
+        results
+    }
+
+    /// Same as [`KdSliceN::within_into`](crate::KdSliceN::within_into).
+    pub fn within_into<'a>(
+        &'a self,
+        query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2],
+        results: &mut Vec<&'a T>,
+    ) {
+        results.clear();
+        self.for_each_within(query, |item| results.push(item));
+    }
+
+    /// Same as [`KdSliceN::for_each_within`](crate::KdSliceN::for_each_within).
+    pub fn for_each_within<'a>(
+        &'a self,
+        query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2],
+        mut f: impl FnMut(&'a T),
+    ) {
+        assert!((0..N::to_usize()).all(|k| query[0].at(k) <= query[1].at(k)));
+        kd_for_each_within_by_cmp(
+            &self.kdtree,
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            self.cached(|a, k| range_compare(a, query[0].at(k), query[1].at(k))),
+            |item, _| f(item),
+        );
+    }
+
+    /// Same as [`KdSliceN::count_within`](crate::KdSliceN::count_within).
+    pub fn count_within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> usize {
+        assert!((0..N::to_usize()).all(|k| query[0].at(k) <= query[1].at(k)));
+        let dim = N::to_usize();
+        kd_count_within_by_cmp(
+            &self.kdtree,
+            dim,
+            self.kdtree.split_axes(),
+            self.cached(|a, k| range_compare(a, query[0].at(k), query[1].at(k))),
+            |_, _| true,
+            |bounds| {
+                (0..dim).all(|k| {
+                    bounds[k].is_some_and(|(_, min)| query[0].at(k) <= self.row(min)[k])
+                        && bounds[dim + k]
+                            .is_some_and(|(_, max)| self.row(max)[k] <= query[1].at(k))
+                })
+            },
+        )
+    }
+
+    /// Same as [`KdSliceN::any_within`](crate::KdSliceN::any_within).
+    pub fn any_within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> bool {
+        assert!((0..N::to_usize()).all(|k| query[0].at(k) <= query[1].at(k)));
+        kd_any_within_by_cmp(
+            &self.kdtree,
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            self.cached(|a, k| range_compare(a, query[0].at(k), query[1].at(k))),
+            |_, _| true,
+        )
+    }
+
+    /// search items within a sphere.
+    pub fn within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> Vec<&T> {
+        let mut results = Vec::new();
+        self.within_radius_into(query, radius, &mut results);
+        results
+    }
+
+    /// Same as [`KdSliceN::within_radius_into`](crate::KdSliceN::within_radius_into).
+    pub fn within_radius_into<'a>(
+        &'a self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        results: &mut Vec<&'a T>,
+    ) {
+        results.clear();
+        self.for_each_within_radius(query, radius, |item| results.push(item));
+    }
+
+    /// Same as [`KdSliceN::for_each_within_radius`](crate::KdSliceN::for_each_within_radius).
+    pub fn for_each_within_radius<'a>(
+        &'a self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        f: impl FnMut(&'a T),
+    ) {
+        self.for_each_within_distance_with_metric(query, radius * radius, &SquaredEuclidean, f)
+    }
+
+    /// Same as [`KdSliceN::count_within_radius`](crate::KdSliceN::count_within_radius).
+    pub fn count_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> usize {
+        let dim = N::to_usize();
+        let squared_radius = radius * radius;
+        kd_count_within_by_cmp(
+            &self.kdtree,
+            dim,
+            self.kdtree.split_axes(),
+            self.cached(|a, k| radius_compare(query.at(k) - a, squared_radius)),
+            |_, position| self.distance(query, &SquaredEuclidean, position) < squared_radius,
+            |bounds| {
+                let mut squared_distance = Scalar::zero();
+                for k in 0..dim {
+                    let (min, max) = match (bounds[k], bounds[dim + k]) {
+                        (Some((_, min)), Some((_, max))) => (self.row(min)[k], self.row(max)[k]),
+                        _ => return false,
+                    };
+                    let x = query.at(k);
+                    let diff = if x - min > max - x { x - min } else { max - x };
+                    squared_distance += diff * diff;
+                }
+                squared_distance < squared_radius
+            },
+        )
+    }
+
+    /// Same as [`KdSliceN::any_within_radius`](crate::KdSliceN::any_within_radius).
+    pub fn any_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> bool {
+        let squared_radius = radius * radius;
+        kd_any_within_by_cmp(
+            &self.kdtree,
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            self.cached(|a, k| radius_compare(query.at(k) - a, squared_radius)),
+            |_, position| self.distance(query, &SquaredEuclidean, position) < squared_radius,
+        )
+    }
+
+    /// Same as [`KdSliceN::within_distance_with_metric`](crate::KdSliceN::within_distance_with_metric).
+    pub fn within_distance_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        distance: Scalar,
+        metric: &M,
+    ) -> Vec<&T> {
+        let mut results = Vec::new();
+        self.for_each_within_distance_with_metric(query, distance, metric, |item| {
+            results.push(item)
+        });
+        results
+    }
+
+    fn for_each_within_distance_with_metric<'a, M: Metric<Scalar>>(
+        &'a self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        distance: Scalar,
+        metric: &M,
+        mut f: impl FnMut(&'a T),
+    ) {
+        kd_for_each_within_by_cmp(
+            &self.kdtree,
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            self.cached(|a, k| {
+                let diff = query.at(k) - a;
+                if metric.axis_distance(k, diff) < distance {
+                    Ordering::Equal
+                } else if diff > Scalar::zero() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }),
+            |item, position| {
+                if self.distance(query, metric, position) < distance {
+                    f(item)
+                }
+            },
+        );
+    }
+
+    /// Same as [`KdSliceN::within_radius_with_distance`](crate::KdSliceN::within_radius_with_distance).
+    pub fn within_radius_with_distance(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        options: WithinRadiusOptions,
+    ) -> Vec<ItemAndDistance<'_, T, Scalar>> {
+        let squared_radius = radius * radius;
+        let mut results = Vec::new();
+        kd_for_each_within_by_cmp(
+            &self.kdtree,
+            N::to_usize(),
+            self.kdtree.split_axes(),
+            self.cached(|a, k| {
+                let diff = query.at(k) - a;
+                if diff * diff <= squared_radius {
+                    Ordering::Equal
+                } else if diff > Scalar::zero() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }),
+            |item, position| {
+                let squared_distance = self.distance(query, &SquaredEuclidean, position);
+                let inside = if options.inclusive {
+                    squared_distance <= squared_radius
+                } else {
+                    squared_distance < squared_radius
+                };
+                if inside {
+                    results.push(ItemAndDistance {
+                        item,
+                        squared_distance,
+                    });
+                }
+            },
+        );
+        if options.sorted {
+            sort_by_distance(&mut results);
+        }
+        results
+    }
+}
+
+/// Compares a coordinate with the range `[min, max]`.
+fn range_compare<Scalar: PartialOrd>(a: Scalar, min: Scalar, max: Scalar) -> Ordering {
+    if a < min {
+        Ordering::Less
+    } else if a > max {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// A [`KdIndexTreeN`] which keeps a contiguous copy of the coordinates of the source items, in the kd-tree order.
+///
+/// The queries read the coordinates from the copy instead of going through the source by the indices.
+/// It is returned by [`KdIndexTreeN::with_coords`] and [`KdIndexTreeN::with_coords_by`].
+/// # Example
+/// ```
+/// let points: Vec<[f64; 3]> = vec![[1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]];
+/// let kdtree = kd_tree::KdIndexTree::build_by_ordered_float(&points).with_coords();
+/// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdCoordsIndexTreeN<'a, T, Scalar, N: Unsigned> {
+    source: &'a [T],
+    kdtree: KdCoordsTreeN<usize, Scalar, N>,
+}
+pub type KdCoordsIndexTree<'a, T> =
+    KdCoordsIndexTreeN<'a, T, <T as KdPoint>::Scalar, <T as KdPoint>::Dim>;
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    /// Returns the kd-tree keeping a contiguous copy of the coordinates given by `coord`.
+    pub fn with_coords_by<Scalar: Copy>(
+        self,
+        coord: impl Fn(&T, usize) -> Scalar,
+    ) -> KdCoordsIndexTreeN<'a, T, Scalar, N> {
+        let source = self.source;
+        KdCoordsIndexTreeN {
+            source,
+            kdtree: self
+                .kdtree
+                .with_coords_by(|&index, k| coord(&source[index], k)),
+        }
+    }
+
+    /// Returns the kd-tree keeping a contiguous copy of the coordinates of the points.
+    pub fn with_coords(self) -> KdCoordsIndexTreeN<'a, T, T::Scalar, N>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.with_coords_by(|item, k| item.at(k))
+    }
+}
+
+impl<'a, T, Scalar, N: Unsigned> KdCoordsIndexTreeN<'a, T, Scalar, N>
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    pub fn source(&self) -> &'a [T] {
+        self.source
+    }
+
+    pub fn indices(&self) -> &KdCoordsTreeN<usize, Scalar, N> {
+        &self.kdtree
+    }
+
+    pub fn item(&self, i: usize) -> &'a T {
+        &self.source[i]
+    }
+
+    /// Drops the copy of the coordinates.
+    pub fn into_inner(self) -> KdIndexTreeN<'a, T, N> {
+        KdIndexTreeN {
+            source: self.source,
+            kdtree: self.kdtree.into_inner(),
+        }
+    }
+
+    pub fn nearest(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+    ) -> Option<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearest(query)
+    }
+
+    pub fn nearest_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        metric: &M,
+    ) -> Option<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearest_with_metric(query, metric)
+    }
+
+    pub fn nearest_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        predicate: impl Fn(&usize) -> bool + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearest_filtered(query, predicate)
+    }
+
+    pub fn nearest_approx(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        eps: Scalar,
+    ) -> Option<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearest_approx(query, eps)
+    }
+
+    pub fn nearest_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        max_visits: usize,
+    ) -> Option<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearest_with_budget(query, max_visits)
+    }
+
+    pub fn nearests(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests(query, num)
+    }
+
+    pub fn nearests_unsorted(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_unsorted(query, num)
+    }
+
+    pub fn nearests_into<'b>(
+        &'b self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'b, usize, Scalar>>,
+    ) {
+        self.kdtree.nearests_into(query, num, nearests)
+    }
+
+    pub fn nearests_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_with_metric(query, num, metric)
+    }
+
+    pub fn nearests_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        max_radius: Scalar,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_within_radius(query, num, max_radius)
+    }
+
+    pub fn nearests_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        predicate: impl Fn(&usize) -> bool + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_filtered(query, num, predicate)
+    }
+
+    pub fn nearests_approx(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        eps: Scalar,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_approx(query, num, eps)
+    }
+
+    pub fn nearests_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        num: usize,
+        max_visits: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree.nearests_with_budget(query, num, max_visits)
+    }
+
+    pub fn within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> Vec<&usize> {
+        self.kdtree.within(query)
+    }
+
+    pub fn within_into<'b>(
+        &'b self,
+        query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2],
+        results: &mut Vec<&'b usize>,
+    ) {
+        self.kdtree.within_into(query, results)
+    }
+
+    pub fn for_each_within<'b>(
+        &'b self,
+        query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2],
+        f: impl FnMut(&'b usize),
+    ) {
+        self.kdtree.for_each_within(query, f)
+    }
+
+    pub fn count_within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> usize {
+        self.kdtree.count_within(query)
+    }
+
+    pub fn any_within(&self, query: &[impl KdPoint<Scalar = Scalar, Dim = N>; 2]) -> bool {
+        self.kdtree.any_within(query)
+    }
+
+    pub fn within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> Vec<&usize> {
+        self.kdtree.within_radius(query, radius)
+    }
+
+    pub fn within_radius_into<'b>(
+        &'b self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        results: &mut Vec<&'b usize>,
+    ) {
+        self.kdtree.within_radius_into(query, radius, results)
+    }
+
+    pub fn for_each_within_radius<'b>(
+        &'b self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        f: impl FnMut(&'b usize),
+    ) {
+        self.kdtree.for_each_within_radius(query, radius, f)
+    }
+
+    pub fn count_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> usize {
+        self.kdtree.count_within_radius(query, radius)
+    }
+
+    pub fn any_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+    ) -> bool {
+        self.kdtree.any_within_radius(query, radius)
+    }
+
+    pub fn within_distance_with_metric<M: Metric<Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        distance: Scalar,
+        metric: &M,
+    ) -> Vec<&usize> {
+        self.kdtree
+            .within_distance_with_metric(query, distance, metric)
+    }
+
+    pub fn within_radius_with_distance(
+        &self,
+        query: &impl KdPoint<Scalar = Scalar, Dim = N>,
+        radius: Scalar,
+        options: WithinRadiusOptions,
+    ) -> Vec<ItemAndDistance<'_, usize, Scalar>> {
+        self.kdtree
+            .within_radius_with_distance(query, radius, options)
+    }
+}
@@ -0,0 +1,397 @@
+use crate::nearest::{distance_by, kd_nearest_approx_by};
+use crate::nearests::kd_nearests_approx_by;
+use crate::radius_compare;
+use crate::sort::{kd_sort_by, Axes};
+use crate::within::kd_within_by_cmp;
+use crate::{ItemAndDistance, SquaredEuclidean};
+use std::cmp::Ordering;
+
+/// A slice of kd-tree whose dimension is given at runtime, instead of `KdPoint::Dim`.
+/// This type implements [`std::ops::Deref`] to `[T]`.
+/// For an owned version of this type, see [`DynKdTree`].
+/// # Example
+/// ```
+/// let mut items: Vec<Vec<f64>> = vec![vec![1.0, 2.0, 3.0], vec![3.0, 1.0, 2.0], vec![2.0, 3.0, 1.0]];
+/// let kdtree = kd_tree::DynKdSlice::sort_by_ordered_float(&mut items, 3);
+/// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+pub struct DynKdSlice<'a, T> {
+    dim: usize,
+    items: &'a [T],
+}
+
+impl<T> Clone for DynKdSlice<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for DynKdSlice<'_, T> {}
+
+impl<T> std::ops::Deref for DynKdSlice<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.items
+    }
+}
+
+impl<'a, T> DynKdSlice<'a, T> {
+    /// # Safety
+    /// `items` must be sorted by [`DynKdSlice::sort_by`] etc. with the same `dim`.
+    pub unsafe fn new_unchecked(items: &'a [T], dim: usize) -> Self {
+        Self { dim, items }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn items(&self) -> &'a [T] {
+        self.items
+    }
+
+    /// # Panics
+    /// Panics if `dim` is zero.
+    pub fn sort_by<F>(items: &'a mut [T], dim: usize, compare: F) -> Self
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        assert!(dim > 0, "dimension must be positive");
+        kd_sort_by(items, dim, compare);
+        Self { dim, items }
+    }
+
+    pub fn sort_by_key<Key, F>(items: &'a mut [T], dim: usize, kd_key: F) -> Self
+    where
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy,
+    {
+        Self::sort_by(items, dim, |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    /// # Panics
+    /// Panics if the length of any row is not `dim`.
+    pub fn sort_by_ordered_float<S>(rows: &'a mut [T], dim: usize) -> Self
+    where
+        T: AsRef<[S]>,
+        S: num_traits::Float,
+    {
+        check_rows(rows, dim);
+        Self::sort_by_key(rows, dim, |row, k| {
+            ordered_float::OrderedFloat(row.as_ref()[k])
+        })
+    }
+
+    /// # Panics
+    /// Panics if the length of any row is not `dim`.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<&[i32]> = vec![&[1, 2, 3], &[3, 1, 2], &[2, 3, 1]];
+    /// let kdtree = kd_tree::DynKdSlice::sort(&mut items, 3);
+    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
+    /// ```
+    pub fn sort<S>(rows: &'a mut [T], dim: usize) -> Self
+    where
+        T: AsRef<[S]>,
+        S: Ord + Copy,
+    {
+        check_rows(rows, dim);
+        Self::sort_by_key(rows, dim, |row, k| row.as_ref()[k])
+    }
+
+    /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
+    /// # Panics
+    /// Panics if the length of `query` is not `self.dim()`.
+    pub fn nearest_by<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+        coord: impl Fn(&T, usize) -> S + Copy,
+    ) -> Option<ItemAndDistance<'a, T, S>> {
+        self.check_query(query);
+        if self.is_empty() {
+            None
+        } else {
+            Some(kd_nearest_approx_by(
+                self.items,
+                |k| query[k],
+                self.dim,
+                Axes::RoundRobin,
+                &SquaredEuclidean,
+                S::one(),
+                usize::MAX,
+                coord,
+            ))
+        }
+    }
+
+    /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
+    pub fn nearest<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+    ) -> Option<ItemAndDistance<'a, T, S>>
+    where
+        T: AsRef<[S]>,
+    {
+        self.nearest_by(query, |row, k| row.as_ref()[k])
+    }
+
+    /// Returns `num` nearest items sorted by the distance from the input point.
+    /// # Panics
+    /// Panics if the length of `query` is not `self.dim()`.
+    pub fn nearests_by<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+        num: usize,
+        coord: impl Fn(&T, usize) -> S + Copy,
+    ) -> Vec<ItemAndDistance<'a, T, S>> {
+        self.check_query(query);
+        kd_nearests_approx_by(
+            self.items,
+            |k| query[k],
+            self.dim,
+            Axes::RoundRobin,
+            num,
+            &SquaredEuclidean,
+            S::one(),
+            usize::MAX,
+            None,
+            |_| true,
+            coord,
+        )
+    }
+
+    /// Returns `num` nearest items sorted by the distance from the input point.
+    /// # Example
+    /// ```
+    /// let mut items = vec![vec![1, 2, 3], vec![3, 1, 2], vec![2, 3, 1]];
+    /// let kdtree = kd_tree::DynKdSlice::sort(&mut items, 3);
+    /// let found = kdtree.nearests(&[3, 1, 2], 2);
+    /// assert_eq!(found[0].item, &[3, 1, 2]);
+    /// assert_eq!(found[1].item, &[2, 3, 1]);
+    /// ```
+    pub fn nearests<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+        num: usize,
+    ) -> Vec<ItemAndDistance<'a, T, S>>
+    where
+        T: AsRef<[S]>,
+    {
+        self.nearests_by(query, num, |row, k| row.as_ref()[k])
+    }
+
+    pub fn within_by_cmp(&self, compare: impl Fn(&T, usize) -> Ordering + Copy) -> Vec<&'a T> {
+        kd_within_by_cmp(self.items, self.dim, Axes::RoundRobin, compare)
+    }
+
+    /// search items within a rectangular region given by its `min` and `max` corners.
+    /// # Panics
+    /// Panics if the length of `min` or `max` is not `self.dim()`.
+    pub fn within_by<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        min: &[S],
+        max: &[S],
+        coord: impl Fn(&T, usize) -> S + Copy,
+    ) -> Vec<&'a T> {
+        self.check_query(min);
+        self.check_query(max);
+        assert!((0..self.dim).all(|k| min[k] <= max[k]));
+        self.within_by_cmp(|item, k| {
+            let a = coord(item, k);
+            if a < min[k] {
+                Ordering::Less
+            } else if a > max[k] {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+    }
+
+    /// search items within a rectangular region given by its `min` and `max` corners.
+    pub fn within<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        min: &[S],
+        max: &[S],
+    ) -> Vec<&'a T>
+    where
+        T: AsRef<[S]>,
+    {
+        self.within_by(min, max, |row, k| row.as_ref()[k])
+    }
+
+    /// search items within a sphere.
+    /// # Panics
+    /// Panics if the length of `query` is not `self.dim()`.
+    pub fn within_radius_by<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+        radius: S,
+        coord: impl Fn(&T, usize) -> S + Copy,
+    ) -> Vec<&'a T> {
+        self.check_query(query);
+        let squared_radius = radius * radius;
+        let mut results =
+            self.within_by_cmp(|item, k| radius_compare(query[k] - coord(item, k), squared_radius));
+        results.retain(|item| {
+            distance_by(|k| query[k], self.dim, *item, &SquaredEuclidean, coord) < squared_radius
+        });
+        results
+    }
+
+    /// search items within a sphere.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<Vec<f32>> = vec![vec![0.0, 0.0], vec![3.0, 0.0], vec![1.0, 1.0]];
+    /// let kdtree = kd_tree::DynKdSlice::sort_by_ordered_float(&mut items, 2);
+    /// assert_eq!(kdtree.within_radius(&[0.0, 0.0], 2.0).len(), 2);
+    /// ```
+    pub fn within_radius<S: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        query: &[S],
+        radius: S,
+    ) -> Vec<&'a T>
+    where
+        T: AsRef<[S]>,
+    {
+        self.within_radius_by(query, radius, |row, k| row.as_ref()[k])
+    }
+
+    fn check_query<S>(&self, query: &[S]) {
+        assert_eq!(
+            query.len(),
+            self.dim,
+            "query dimension differs from the kd-tree"
+        );
+    }
+}
+
+/// An owned kd-tree whose dimension is given at runtime, instead of `KdPoint::Dim`.
+/// The queries are provided by [`DynKdSlice`], which is returned by [`DynKdTree::as_slice`].
+/// # Example
+/// ```
+/// let rows: Vec<Vec<f64>> = vec![vec![1.0, 2.0, 3.0], vec![3.0, 1.0, 2.0], vec![2.0, 3.0, 1.0]];
+/// let kdtree = kd_tree::DynKdTree::build_by_ordered_float(rows, 3);
+/// assert_eq!(kdtree.as_slice().nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynKdTree<T> {
+    dim: usize,
+    items: Vec<T>,
+}
+
+impl<T> std::ops::Deref for DynKdTree<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T> From<DynKdTree<T>> for Vec<T> {
+    fn from(src: DynKdTree<T>) -> Self {
+        src.items
+    }
+}
+
+impl<T> DynKdTree<T> {
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn as_slice(&self) -> DynKdSlice<'_, T> {
+        DynKdSlice {
+            dim: self.dim,
+            items: &self.items,
+        }
+    }
+
+    /// # Panics
+    /// Panics if `dim` is zero.
+    pub fn build_by<F>(mut items: Vec<T>, dim: usize, compare: F) -> Self
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        DynKdSlice::sort_by(&mut items, dim, compare);
+        Self { dim, items }
+    }
+
+    pub fn build_by_key<Key, F>(items: Vec<T>, dim: usize, kd_key: F) -> Self
+    where
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy,
+    {
+        Self::build_by(items, dim, |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    /// # Panics
+    /// Panics if the length of any row is not `dim`.
+    pub fn build_by_ordered_float<S>(mut rows: Vec<T>, dim: usize) -> Self
+    where
+        T: AsRef<[S]>,
+        S: num_traits::Float,
+    {
+        DynKdSlice::sort_by_ordered_float(&mut rows, dim);
+        Self { dim, items: rows }
+    }
+
+    /// # Panics
+    /// Panics if the length of any row is not `dim`.
+    pub fn build<S>(mut rows: Vec<T>, dim: usize) -> Self
+    where
+        T: AsRef<[S]>,
+        S: Ord + Copy,
+    {
+        DynKdSlice::sort(&mut rows, dim);
+        Self { dim, items: rows }
+    }
+}
+
+impl<'a, S> DynKdTree<&'a [S]> {
+    /// Builds a kd-tree of rows in a flat row-major `buffer`,
+    /// where the `i`-th row is `buffer[i * stride..i * stride + dim]`.
+    /// The buffer itself is not copied nor reordered.
+    /// # Panics
+    /// Panics if `dim` is greater than `stride`, or the last row is shorter than `dim`.
+    /// # Example
+    /// ```
+    /// // 3 rows of 2 dimensional points, each followed by a padding.
+    /// let buffer = [1.0, 2.0, -1.0, 3.0, 1.0, -1.0, 2.0, 3.0, -1.0];
+    /// let kdtree = kd_tree::DynKdTree::from_flat_by_ordered_float(&buffer, 2, 3);
+    /// assert_eq!(kdtree.len(), 3);
+    /// assert_eq!(kdtree.as_slice().nearest(&[3.1, 0.9]).unwrap().item, &[3.0, 1.0]);
+    /// ```
+    pub fn from_flat_by_ordered_float(buffer: &'a [S], dim: usize, stride: usize) -> Self
+    where
+        S: num_traits::Float,
+    {
+        Self::build_by_ordered_float(flat_rows(buffer, dim, stride), dim)
+    }
+
+    /// Same as [`DynKdTree::from_flat_by_ordered_float`], but for totally ordered scalars.
+    pub fn from_flat(buffer: &'a [S], dim: usize, stride: usize) -> Self
+    where
+        S: Ord + Copy,
+    {
+        Self::build(flat_rows(buffer, dim, stride), dim)
+    }
+}
+
+fn check_rows<T: AsRef<[S]>, S>(rows: &[T], dim: usize) {
+    assert!(
+        rows.iter().all(|row| row.as_ref().len() == dim),
+        "row dimension differs from the kd-tree"
+    );
+}
+
+fn flat_rows<S>(buffer: &[S], dim: usize, stride: usize) -> Vec<&[S]> {
+    assert!(dim <= stride, "stride must not be less than dimension");
+    buffer.chunks(stride).map(|row| &row[..dim]).collect()
+}
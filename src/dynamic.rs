@@ -0,0 +1,327 @@
+use crate::{ItemAndDistance, KdPoint, KdTreeN};
+use std::cmp::Ordering;
+use typenum::Unsigned;
+
+/// A kd-tree which supports insertion and removal of items.
+///
+/// Items are stored in a logarithmic number of static [`KdTreeN`] buckets (the Bentley-Saxe method),
+/// where the `i`-th bucket holds at most `2^i` items.
+/// Inserting an item rebuilds `O(log n)` items in amortized time,
+/// and queries are run on every bucket and their results are merged.
+/// When a removal leaves a bucket less than half full, it is merged with the smaller buckets,
+/// so the number of buckets stays logarithmic in the number of live items.
+///
+/// # Example
+/// ```
+/// let mut kdtree = kd_tree::KdDynamicTree::new();
+/// kdtree.insert([1, 2, 3]);
+/// kdtree.insert([3, 1, 2]);
+/// kdtree.insert([2, 3, 1]);
+/// assert_eq!(kdtree.len(), 3);
+/// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
+/// assert!(kdtree.remove(&[3, 1, 2]));
+/// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[2, 3, 1]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct KdDynamicTreeN<T, N: Unsigned, F = fn(&T, &T, usize) -> Ordering> {
+    buckets: Vec<KdTreeN<T, N>>,
+    compare: F,
+    len: usize,
+}
+pub type KdDynamicTree<T> = KdDynamicTreeN<T, <T as KdPoint>::Dim>;
+
+impl<T, N: Unsigned> KdDynamicTreeN<T, N> {
+    /// # Example
+    /// ```
+    /// let mut kdtree = kd_tree::KdDynamicTree::new_by_ordered_float();
+    /// kdtree.insert([1.0, 2.0, 3.0]);
+    /// kdtree.insert([3.0, 1.0, 2.0]);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+    /// ```
+    pub fn new_by_ordered_float() -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        Self::new_by(|item1, item2, k| {
+            ordered_float::OrderedFloat(item1.at(k)).cmp(&ordered_float::OrderedFloat(item2.at(k)))
+        })
+    }
+
+    pub fn new() -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Ord,
+    {
+        Self::new_by(|item1, item2, k| item1.at(k).cmp(&item2.at(k)))
+    }
+}
+
+impl<T, N: Unsigned, F> KdDynamicTreeN<T, N, F>
+where
+    F: Fn(&T, &T, usize) -> Ordering + Copy,
+{
+    /// # Example
+    /// ```
+    /// struct Item {
+    ///     point: [i32; 3],
+    ///     id: usize,
+    /// }
+    /// let mut kdtree = kd_tree::KdDynamicTreeN::<_, typenum::U3, _>::new_by(
+    ///     |item1: &Item, item2: &Item, k| item1.point[k].cmp(&item2.point[k])
+    /// );
+    /// kdtree.insert(Item { point: [1, 2, 3], id: 111 });
+    /// kdtree.insert(Item { point: [3, 1, 2], id: 222 });
+    /// assert_eq!(kdtree.nearest_by(&[3, 1, 2], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// assert_eq!(kdtree.remove_by(|item| item.id == 222), 1);
+    /// assert_eq!(kdtree.nearest_by(&[3, 1, 2], |item, k| item.point[k]).unwrap().item.id, 111);
+    /// ```
+    pub fn new_by(compare: F) -> Self {
+        Self {
+            buckets: Vec::new(),
+            compare,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the static kd-trees which hold the items.
+    pub fn buckets(&self) -> &[KdTreeN<T, N>] {
+        &self.buckets
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.buckets.iter().flat_map(|bucket| bucket.iter())
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.buckets
+            .into_iter()
+            .flat_map(|bucket| bucket.into_vec())
+            .collect()
+    }
+
+    pub fn insert(&mut self, item: T) {
+        let mut carry = vec![item];
+        for (i, bucket) in self.buckets.iter_mut().enumerate() {
+            if bucket.len() + carry.len() <= 1 << i {
+                carry.append(&mut std::mem::take(bucket).into_vec());
+                *bucket = KdTreeN::build_by(carry, self.compare);
+                self.len += 1;
+                return;
+            }
+            carry.append(&mut std::mem::take(bucket).into_vec());
+        }
+        let mut i = self.buckets.len();
+        while carry.len() > 1 << i {
+            self.buckets.push(KdTreeN::default());
+            i += 1;
+        }
+        self.buckets.push(KdTreeN::build_by(carry, self.compare));
+        self.len += 1;
+    }
+
+    /// Removes an item which is equal to `item`. Returns `false` if no such item is found.
+    pub fn remove(&mut self, item: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let compare = self.compare;
+        for i in 0..self.buckets.len() {
+            let found = self.buckets[i]
+                .within_by_cmp(|x, k| compare(x, item, k))
+                .into_iter()
+                .any(|x| x == item);
+            if found {
+                let mut items = std::mem::take(&mut self.buckets[i]).into_vec();
+                let j = items.iter().position(|x| x == item).unwrap();
+                items.swap_remove(j);
+                self.rebuild_bucket(i, items);
+                self.len -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Removes all items which satisfy the predicate. Returns the number of removed items.
+    pub fn remove_by(&mut self, mut predicate: impl FnMut(&T) -> bool) -> usize {
+        let mut removed = 0;
+        for i in 0..self.buckets.len() {
+            if self.buckets[i].iter().any(&mut predicate) {
+                let mut items = std::mem::take(&mut self.buckets[i]).into_vec();
+                let len = items.len();
+                items.retain(|item| !predicate(item));
+                removed += len - items.len();
+                self.rebuild_bucket(i, items);
+            }
+        }
+        self.len -= removed;
+        removed
+    }
+
+    /// Rebuilds the `i`-th bucket from `items` after a removal.
+    /// If it would be less than half full, the items of the buckets `0..=i` are packed
+    /// into the smallest buckets which can hold them, i.e. into at most two buckets which are more than half full.
+    fn rebuild_bucket(&mut self, i: usize, mut items: Vec<T>) {
+        let capacity = 1 << i;
+        if items.len() * 2 >= capacity {
+            self.buckets[i] = KdTreeN::build_by(items, self.compare);
+            return;
+        }
+        for bucket in &mut self.buckets[..i] {
+            items.append(&mut std::mem::take(bucket).into_vec());
+        }
+        if items.len() > capacity {
+            let rest = items.split_off(capacity);
+            self.buckets[i] = KdTreeN::build_by(items, self.compare);
+            items = rest;
+        }
+        if !items.is_empty() {
+            let j = (0..=i).find(|&j| items.len() <= 1 << j).unwrap();
+            self.buckets[j] = KdTreeN::build_by(items, self.compare);
+        }
+        while self.buckets.last().is_some_and(|bucket| bucket.is_empty()) {
+            self.buckets.pop();
+        }
+    }
+
+    pub fn nearest_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        self.buckets
+            .iter()
+            .filter_map(|bucket| bucket.nearest_by(query, coord))
+            .fold(None, |nearest, found| match nearest {
+                Some(nearest) if nearest.squared_distance <= found.squared_distance => {
+                    Some(nearest)
+                }
+                _ => Some(found),
+            })
+    }
+
+    pub fn nearest(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_by(query, |item, k| item.at(k))
+    }
+
+    pub fn nearests_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        let mut nearests: Vec<_> = self
+            .buckets
+            .iter()
+            .flat_map(|bucket| bucket.nearests_by(query, num, coord))
+            .collect();
+        nearests.sort_by(|a, b| {
+            a.squared_distance
+                .partial_cmp(&b.squared_distance)
+                .unwrap_or(Ordering::Equal)
+        });
+        nearests.truncate(num);
+        nearests
+    }
+
+    /// Returns kNN(k nearest neighbors) from the input point.
+    /// # Example
+    /// ```
+    /// let mut kdtree = kd_tree::KdDynamicTree::new();
+    /// for p in vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]] {
+    ///     kdtree.insert(p);
+    /// }
+    /// let nearests = kdtree.nearests(&[3, 1, 2], 2);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &[3, 1, 2]);
+    /// assert_eq!(nearests[1].item, &[3, 2, 2]);
+    /// ```
+    pub fn nearests(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_by(query, num, |item, k| item.at(k))
+    }
+
+    pub fn within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        self.buckets
+            .iter()
+            .flat_map(|bucket| bucket.within_by(query, coord))
+            .collect()
+    }
+
+    /// search points within a rectangular region
+    pub fn within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_by(query, |item, k| item.at(k))
+    }
+
+    pub fn within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        self.buckets
+            .iter()
+            .flat_map(|bucket| bucket.within_radius_by(query, radius, coord))
+            .collect()
+    }
+
+    /// search points within k-dimensional sphere
+    pub fn within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_by(query, radius, |item, k| item.at(k))
+    }
+}
+
+impl<T: KdPoint<Dim = N>, N: Unsigned> Default for KdDynamicTreeN<T, N>
+where
+    T::Scalar: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, N: Unsigned, F> Extend<T> for KdDynamicTreeN<T, N, F>
+where
+    F: Fn(&T, &T, usize) -> Ordering + Copy,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
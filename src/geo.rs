@@ -0,0 +1,188 @@
+use crate::{Const, KdTreeN};
+
+/// The mean radius of the earth in meters.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// A geographic position in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon }
+    }
+
+    /// Returns the unit vector from the center of the earth (ECEF, on a sphere).
+    pub fn to_unit_vector(self) -> [f64; 3] {
+        let (lat, lon) = (self.lat.to_radians(), self.lon.to_radians());
+        [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+    }
+
+    /// Returns the great-circle distance in meters by the haversine formula.
+    /// # Example
+    /// ```
+    /// use kd_tree::LatLon;
+    /// let paris = LatLon::new(48.8566, 2.3522);
+    /// let london = LatLon::new(51.5074, -0.1278);
+    /// assert!((paris.distance_to(london) - 343_500.0).abs() < 1000.0);
+    /// ```
+    pub fn distance_to(self, other: LatLon) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let hav = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * hav.sqrt().min(1.0).asin()
+    }
+}
+
+/// An item and its great-circle distance in meters, returned by the queries of [`KdGeoTree`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GeoItemAndDistance<'a, T> {
+    pub item: &'a T,
+    pub distance: f64,
+}
+
+/// A kd-tree of geographic positions.
+///
+/// The items are indexed by the unit vectors of their positions, so the searches work
+/// near the poles and across the antimeridian, where the latitude and the longitude are discontinuous.
+/// The distances are the great-circle distances in meters on a spherical earth of [`EARTH_RADIUS`].
+/// # Example
+/// ```
+/// use kd_tree::{KdGeoTree, LatLon};
+/// let cities = vec![
+///     ("Tokyo", LatLon::new(35.6812, 139.7671)),
+///     ("Anchorage", LatLon::new(61.2181, -149.9003)),
+///     ("Fiji", LatLon::new(-18.1248, 178.4501)),
+/// ];
+/// let kdtree = KdGeoTree::build_by_key(cities, |city| city.1);
+/// // Samoa is on the other side of the antimeridian from Fiji.
+/// let found = kdtree.nearest(LatLon::new(-13.7590, -172.1046)).unwrap();
+/// assert_eq!(found.item.0, "Fiji");
+/// assert!((found.distance - 1_150_000.0).abs() < 50_000.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdGeoTree<T> {
+    kdtree: KdTreeN<([f64; 3], T), Const<3>>,
+}
+
+impl<T> Default for KdGeoTree<T> {
+    fn default() -> Self {
+        Self {
+            kdtree: Default::default(),
+        }
+    }
+}
+
+impl<T> KdGeoTree<T> {
+    /// Builds the kd-tree of the items, whose positions are given by `latlon`.
+    /// # Panics
+    /// Panics if any position is not finite.
+    pub fn build_by_key(items: Vec<T>, latlon: impl Fn(&T) -> LatLon) -> Self {
+        let items = items
+            .into_iter()
+            .map(|item| (latlon(&item).to_unit_vector(), item))
+            .collect();
+        Self {
+            kdtree: KdTreeN::build_by_ordered_float(items),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.kdtree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kdtree.is_empty()
+    }
+
+    /// Returns the items in the kd-tree order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.kdtree.iter().map(|(_, item)| item)
+    }
+
+    /// Returns the nearest item from the query.
+    /// Returns `None` if `self.is_empty()`.
+    pub fn nearest(&self, query: LatLon) -> Option<GeoItemAndDistance<'_, T>> {
+        self.kdtree
+            .nearest(&query.to_unit_vector())
+            .map(|found| GeoItemAndDistance {
+                item: &found.item.1,
+                distance: arc_length(found.squared_distance),
+            })
+    }
+
+    /// Returns `num` nearest items from the query, sorted by the distance.
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdGeoTree, LatLon};
+    /// let kdtree = KdGeoTree::build(vec![
+    ///     LatLon::new(89.9, 0.0),
+    ///     LatLon::new(89.9, 180.0),
+    ///     LatLon::new(89.0, 90.0),
+    /// ]);
+    /// // both points near the north pole are nearer than the third one, whatever their longitudes.
+    /// let found = kdtree.nearests(LatLon::new(89.9, 90.0), 2);
+    /// assert!(found.iter().all(|found| found.item.lat == 89.9));
+    /// ```
+    pub fn nearests(&self, query: LatLon, num: usize) -> Vec<GeoItemAndDistance<'_, T>> {
+        self.kdtree
+            .nearests(&query.to_unit_vector(), num)
+            .into_iter()
+            .map(|found| GeoItemAndDistance {
+                item: &found.item.1,
+                distance: arc_length(found.squared_distance),
+            })
+            .collect()
+    }
+
+    /// Returns the items whose distances from the query are less than `distance` meters, in no particular order.
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdGeoTree, LatLon};
+    /// let kdtree = KdGeoTree::build(vec![LatLon::new(0.0, 179.99), LatLon::new(0.0, -179.99), LatLon::new(0.0, 0.0)]);
+    /// assert_eq!(kdtree.within_distance(LatLon::new(0.0, 180.0), 2_000.0).len(), 2);
+    /// ```
+    pub fn within_distance(&self, query: LatLon, distance: f64) -> Vec<GeoItemAndDistance<'_, T>> {
+        let query = query.to_unit_vector();
+        let angle = distance / EARTH_RADIUS;
+        let candidates = if angle > std::f64::consts::PI {
+            // the whole sphere.
+            self.kdtree.iter().collect()
+        } else {
+            // the chord subtending the angle, with a margin for the rounding errors.
+            let chord = 2.0 * (angle / 2.0).sin() * (1.0 + 1e-12);
+            self.kdtree.within_radius(&query, chord)
+        };
+        candidates
+            .into_iter()
+            .map(|(point, item)| GeoItemAndDistance {
+                item,
+                distance: arc_length(squared_distance(point, &query)),
+            })
+            .filter(|found| found.distance < distance)
+            .collect()
+    }
+}
+
+impl KdGeoTree<LatLon> {
+    /// # Panics
+    /// Panics if any position is not finite.
+    pub fn build(points: Vec<LatLon>) -> Self {
+        Self::build_by_key(points, |&point| point)
+    }
+}
+
+/// Converts the squared chord between unit vectors into the great-circle distance in meters.
+fn arc_length(squared_chord: f64) -> f64 {
+    2.0 * EARTH_RADIUS * (squared_chord.sqrt() / 2.0).min(1.0).asin()
+}
+
+fn squared_distance(p1: &[f64; 3], p2: &[f64; 3]) -> f64 {
+    (0..3).map(|k| (p1[k] - p2[k]).powi(2)).sum()
+}
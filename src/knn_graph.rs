@@ -0,0 +1,374 @@
+use crate::nearests::kd_nearests_approx_by;
+use crate::sort::Axes;
+use crate::{KdIndexTreeN, KdPoint, SquaredEuclidean};
+use typenum::Unsigned;
+
+/// How to make a k-nearest-neighbor graph undirected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Symmetrize {
+    /// Each item is adjacent to its own `k` nearest neighbors only.
+    #[default]
+    Directed,
+    /// Two items are adjacent if each is one of the `k` nearest neighbors of the other.
+    Mutual,
+    /// Two items are adjacent if either is one of the `k` nearest neighbors of the other.
+    Union,
+}
+
+/// Options for [`KdSliceN::knn_graph_with_options`](crate::KdSliceN::knn_graph_with_options).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KnnGraphOptions {
+    pub symmetrize: Symmetrize,
+}
+
+/// A k-nearest-neighbor graph in the compressed sparse row (CSR) format.
+///
+/// The neighbors of the item `i` are `neighbors[offsets[i]..offsets[i + 1]]`,
+/// sorted by the squared distance. An item is never a neighbor of itself, even if it has duplicates.
+/// # Example
+/// ```
+/// let kdtree = kd_tree::KdTree::build(vec![[0, 0], [1, 0], [1, 0], [5, 5]]);
+/// let graph = kdtree.knn_graph(1);
+/// for i in 0..graph.len() {
+///     // the duplicates are the nearest neighbors of each other.
+///     if kdtree[i] == [1, 0] {
+///         assert_eq!(kdtree[graph.neighbors(i)[0]], [1, 0]);
+///         assert_eq!(graph.squared_distances(i), &[0]);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KnnGraph<Scalar> {
+    offsets: Vec<usize>,
+    neighbors: Vec<usize>,
+    squared_distances: Vec<Scalar>,
+}
+
+/// The graph of no items.
+impl<Scalar> Default for KnnGraph<Scalar> {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            neighbors: Vec::new(),
+            squared_distances: Vec::new(),
+        }
+    }
+}
+
+impl<Scalar> KnnGraph<Scalar> {
+    /// Returns the number of the items.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of the edges, counting each direction of the undirected edges.
+    pub fn num_edges(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Returns the neighbors of the item `i`, sorted by the distance.
+    pub fn neighbors(&self, i: usize) -> &[usize] {
+        &self.neighbors[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    /// Returns the squared distances to the neighbors of the item `i`.
+    pub fn squared_distances(&self, i: usize) -> &[Scalar] {
+        &self.squared_distances[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    /// Returns the graph of the parts, or the reason why they are not a valid graph.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_parts(
+        offsets: Vec<usize>,
+        neighbors: Vec<usize>,
+        squared_distances: Vec<Scalar>,
+    ) -> Result<Self, &'static str> {
+        if offsets.first() != Some(&0) {
+            return Err("offsets must start with 0");
+        }
+        if offsets.windows(2).any(|w| w[0] > w[1]) {
+            return Err("offsets must be non-decreasing");
+        }
+        if offsets.last() != Some(&neighbors.len()) {
+            return Err("offsets must end with the number of the neighbors");
+        }
+        if squared_distances.len() != neighbors.len() {
+            return Err("the numbers of the neighbors and the squared distances differ");
+        }
+        if neighbors.iter().any(|&j| j >= offsets.len() - 1) {
+            return Err("neighbors must be less than the number of the items");
+        }
+        Ok(Self {
+            offsets,
+            neighbors,
+            squared_distances,
+        })
+    }
+}
+
+impl<Scalar: Copy + PartialOrd> KnnGraph<Scalar> {
+    fn from_rows(mut rows: Vec<Vec<(usize, Scalar)>>, symmetrize: Symmetrize) -> Self {
+        match symmetrize {
+            Symmetrize::Directed => {}
+            Symmetrize::Mutual => {
+                let directed = rows.clone();
+                for (i, row) in rows.iter_mut().enumerate() {
+                    row.retain(|&(j, _)| directed[j].iter().any(|&(k, _)| k == i));
+                }
+            }
+            Symmetrize::Union => {
+                for i in 0..rows.len() {
+                    for e in 0..rows[i].len() {
+                        let (j, d) = rows[i][e];
+                        if !rows[j].iter().any(|&(k, _)| k == i) {
+                            rows[j].push((i, d));
+                        }
+                    }
+                }
+                for row in &mut rows {
+                    sort_row(row);
+                }
+            }
+        }
+        let mut graph = Self {
+            offsets: Vec::with_capacity(rows.len() + 1),
+            neighbors: Vec::new(),
+            squared_distances: Vec::new(),
+        };
+        graph.offsets.push(0);
+        for row in rows {
+            for (j, d) in row {
+                graph.neighbors.push(j);
+                graph.squared_distances.push(d);
+            }
+            graph.offsets.push(graph.neighbors.len());
+        }
+        graph
+    }
+}
+
+fn sort_row<Scalar: PartialOrd>(row: &mut [(usize, Scalar)]) {
+    row.sort_by(|a, b| {
+        a.1.partial_cmp(&b.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+}
+
+/// Returns the `k` nearest items to the item `i` except itself, where `indices` is a kd-tree of item indices
+/// whose nodes are split along `axes`.
+fn knn_row<S: num_traits::NumAssign + Copy + PartialOrd>(
+    indices: &[usize],
+    dim: usize,
+    axes: Axes<'_>,
+    k: usize,
+    i: usize,
+    get: impl Fn(usize, usize) -> S + Copy,
+) -> Vec<(usize, S)> {
+    let mut row: Vec<_> = kd_nearests_approx_by(
+        indices,
+        |k| get(i, k),
+        dim,
+        axes,
+        k,
+        &SquaredEuclidean,
+        S::one(),
+        usize::MAX,
+        None,
+        |&j| j != i,
+        |&j, k| get(j, k),
+    )
+    .into_iter()
+    .map(|found| (*found.item, found.squared_distance))
+    .collect();
+    sort_row(&mut row);
+    row
+}
+
+impl_kd_queries! {
+    /// Returns the `k` nearest other items of every item, indexed by the positions in this slice.
+    pub fn knn_graph_by<Scalar: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> KnnGraph<Scalar> {
+        let dim = N::to_usize();
+        let indices: Vec<usize> = (0..self.len()).collect();
+        let (items, axes) = (self.items(), self.split_axes());
+        let get = move |i: usize, axis: usize| coord(&items[i], axis);
+        let rows = (0..self.len())
+            .map(|i| knn_row(&indices, dim, axes, k, i, get))
+            .collect();
+        KnnGraph::from_rows(rows, options.symmetrize)
+    }
+
+    /// Returns the `k` nearest other items of every item, indexed by the positions in this slice.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[0.0, 0.0], [1.0, 0.0], [3.0, 0.0]]);
+    /// let graph = kdtree.knn_graph(1);
+    /// assert_eq!(graph.len(), 3);
+    /// assert_eq!(graph.num_edges(), 3);
+    /// let i = kdtree.iter().position(|p| p == &[3.0, 0.0]).unwrap();
+    /// assert_eq!(kdtree[graph.neighbors(i)[0]], [1.0, 0.0]);
+    /// ```
+    pub fn knn_graph(&self, k: usize) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.knn_graph_with_options(k, KnnGraphOptions::default())
+    }
+
+    /// Same as [`KdSliceN::knn_graph`](crate::KdSliceN::knn_graph), but may make the graph undirected.
+    /// # Example
+    /// ```
+    /// use kd_tree::{KnnGraphOptions, Symmetrize};
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[0.0, 0.0], [1.0, 0.0], [3.0, 0.0]]);
+    /// let options = |symmetrize| KnnGraphOptions { symmetrize };
+    /// assert_eq!(kdtree.knn_graph_with_options(1, options(Symmetrize::Mutual)).num_edges(), 2);
+    /// assert_eq!(kdtree.knn_graph_with_options(1, options(Symmetrize::Union)).num_edges(), 4);
+    /// ```
+    pub fn knn_graph_with_options(&self, k: usize, options: KnnGraphOptions) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.knn_graph_by(k, options, |item, k| item.at(k))
+    }
+
+    /// Same as [`KdSliceN::knn_graph_by`], but searches the neighbors in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph_by<Scalar>(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+        coord: impl Fn(&T, usize) -> Scalar + Copy + Send + Sync,
+    ) -> KnnGraph<Scalar>
+    where
+        T: Sync,
+        Scalar: num_traits::NumAssign + Copy + PartialOrd + Send,
+    {
+        use rayon::prelude::*;
+        let dim = N::to_usize();
+        let indices: Vec<usize> = (0..self.len()).collect();
+        let (items, axes) = (self.items(), self.split_axes());
+        let get = move |i: usize, axis: usize| coord(&items[i], axis);
+        let rows = (0..self.len())
+            .into_par_iter()
+            .map(|i| knn_row(&indices, dim, axes, k, i, get))
+            .collect();
+        KnnGraph::from_rows(rows, options.symmetrize)
+    }
+
+    /// Same as [`KdSliceN::knn_graph`], but searches the neighbors in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph(&self, k: usize) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_knn_graph_with_options(k, KnnGraphOptions::default())
+    }
+
+    /// Same as [`KdSliceN::knn_graph_with_options`], but searches the neighbors in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph_with_options(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+    ) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_knn_graph_by(k, options, |item, k| item.at(k))
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    /// Returns the `k` nearest other items of every item, indexed by the positions in the source.
+    pub fn knn_graph_by<Scalar: num_traits::NumAssign + Copy + PartialOrd>(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> KnnGraph<Scalar> {
+        let dim = N::to_usize();
+        let (source, indices) = (self.source(), self.indices());
+        let (axes, indices) = (indices.split_axes(), indices.items());
+        let get = move |i: usize, axis: usize| coord(&source[i], axis);
+        let rows = (0..source.len())
+            .map(|i| knn_row(indices, dim, axes, k, i, get))
+            .collect();
+        KnnGraph::from_rows(rows, options.symmetrize)
+    }
+
+    /// Returns the `k` nearest other items of every item, indexed by the positions in the source.
+    pub fn knn_graph(&self, k: usize) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.knn_graph_with_options(k, KnnGraphOptions::default())
+    }
+
+    pub fn knn_graph_with_options(&self, k: usize, options: KnnGraphOptions) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.knn_graph_by(k, options, |item, k| item.at(k))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph_by<Scalar>(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+        coord: impl Fn(&T, usize) -> Scalar + Copy + Send + Sync,
+    ) -> KnnGraph<Scalar>
+    where
+        T: Sync,
+        Scalar: num_traits::NumAssign + Copy + PartialOrd + Send,
+    {
+        use rayon::prelude::*;
+        let dim = N::to_usize();
+        let (source, indices) = (self.source(), self.indices());
+        let (axes, indices) = (indices.split_axes(), indices.items());
+        let get = move |i: usize, axis: usize| coord(&source[i], axis);
+        let rows = (0..source.len())
+            .into_par_iter()
+            .map(|i| knn_row(indices, dim, axes, k, i, get))
+            .collect();
+        KnnGraph::from_rows(rows, options.symmetrize)
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph(&self, k: usize) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_knn_graph_with_options(k, KnnGraphOptions::default())
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_knn_graph_with_options(
+        &self,
+        k: usize,
+        options: KnnGraphOptions,
+    ) -> KnnGraph<T::Scalar>
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Send,
+    {
+        self.par_knn_graph_by(k, options, |item, k| item.at(k))
+    }
+}
@@ -24,13 +24,63 @@
 //! assert!(found.iter().any(|&&p| p == [1.0, 2.0, 3.0]));
 //! assert!(found.iter().any(|&&p| p == [3.0, 1.0, 2.0]));
 //! ```
+
+/// Implements the queries for both [`KdSliceN`] and [`KdTreeN`], which read the split axes by `self.split_axes()`.
+///
+/// A tree built by [`KdTreeN::build_with_strategy_by`] or the like keeps the split axes of its nodes,
+/// which the slice it derefs to doesn't know, so the tree has its own copy of every query.
+/// The doc tests are collected from the slice only.
+macro_rules! impl_kd_queries {
+    ($(#[$attr:meta])* impl { $($body:tt)* }) => {
+        $(#[$attr])*
+        impl<T, N: typenum::Unsigned> $crate::KdSliceN<T, N> {
+            $($body)*
+        }
+
+        #[cfg(not(doctest))]
+        $(#[$attr])*
+        impl<T, N: typenum::Unsigned> $crate::KdTreeN<T, N> {
+            $($body)*
+        }
+    };
+    ($($body:tt)*) => {
+        impl_kd_queries! { impl { $($body)* } }
+    };
+}
+
+mod batch;
+mod bucket;
+mod coords;
+mod dyn_tree;
+mod dynamic;
+mod geo;
+mod knn_graph;
+mod metric;
 mod nearest;
+mod nearest_iter;
 mod nearests;
+mod periodic;
+mod region;
+mod segment;
+#[cfg(feature = "serde")]
+mod serde_impl;
 mod sort;
 mod tests;
 mod within;
+pub use bucket::{KdBucketTree, KdBucketTreeN, SplitStrategy};
+pub use coords::{KdCoordsIndexTree, KdCoordsIndexTreeN, KdCoordsTree, KdCoordsTreeN};
+pub use dyn_tree::{DynKdSlice, DynKdTree};
+pub use dynamic::*;
+pub use geo::{GeoItemAndDistance, KdGeoTree, LatLon, EARTH_RADIUS};
+pub use knn_graph::{KnnGraph, KnnGraphOptions, Symmetrize};
+pub use metric::*;
 use nearest::*;
+pub use nearest_iter::NearestIter;
 use nearests::*;
+pub use periodic::Periodic;
+pub use region::{Capsule, ConvexPolytope, Ellipsoid, HalfSpace, OrientedBox, Overlap, Region};
+pub use segment::RayHit;
+pub use sort::is_kd_sorted_by;
 use sort::*;
 use std::cmp::Ordering;
 use std::marker::PhantomData;
@@ -66,12 +116,139 @@ pub trait KdPoint {
     fn at(&self, i: usize) -> Self::Scalar;
 }
 
+/// The `typenum` unsigned integer of a const generic dimension, e.g. `Const<3>` is `typenum::U3`.
+///
+/// This is the dimension of fixed-sized arrays `[T; N]`, which implement [`KdPoint`] for `N <= 1024`.
+/// # Example
+/// ```
+/// struct MyItem {
+///     point: [f32; 32],
+///     id: usize,
+/// }
+/// impl kd_tree::KdPoint for MyItem {
+///     type Scalar = f32;
+///     type Dim = kd_tree::Const<32>;
+///     fn at(&self, k: usize) -> f32 { self.point[k] }
+/// }
+/// let kdtree: kd_tree::KdTree<MyItem> = kd_tree::KdTree::build_by_ordered_float(vec![
+///     MyItem { point: [1.0; 32], id: 111 },
+///     MyItem { point: [2.0; 32], id: 222 },
+/// ]);
+/// assert_eq!(kdtree.nearest(&[1.9; 32]).unwrap().item.id, 222);
+/// ```
+pub type Const<const N: usize> = typenum::U<N>;
+
+/// An item found by a query and its distance from the query.
+///
+/// With the `serde` feature, it implements `Serialize` but not `Deserialize`, because it borrows the item.
+/// Deserialize the output into a struct which owns the item, with the fields `item` and `squared_distance`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ItemAndDistance<'a, T, Scalar> {
     pub item: &'a T,
+    /// The squared euclidean distance, or the value of [`Metric::distance`] for `*_with_metric` queries.
+    ///
+    /// Despite the name, the value is not squared for metrics such as [`Manhattan`] or [`Chebyshev`]:
+    /// it is the plain L1 or L∞ distance in that case.
     pub squared_distance: Scalar,
 }
 
+/// Options for [`KdSliceN::within_radius_with_distance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WithinRadiusOptions {
+    /// Sort the results by distance.
+    pub sorted: bool,
+    /// Include the items exactly on the sphere, i.e. `squared_distance <= radius * radius`.
+    pub inclusive: bool,
+}
+
+/// An error returned by `try_*_by_ordered_float`, when a coordinate of an item is NaN or infinite.
+///
+/// [`KdTreeN::try_build_by_ordered_float`] gives the items back by [`NonFiniteCoordinateError::into_inner`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct NonFiniteCoordinateError<T = ()> {
+    /// The index of the item in the input.
+    pub index: usize,
+    /// The axis of the coordinate.
+    pub axis: usize,
+    items: T,
+}
+impl NonFiniteCoordinateError {
+    fn with_items<T>(self, items: T) -> NonFiniteCoordinateError<T> {
+        NonFiniteCoordinateError {
+            index: self.index,
+            axis: self.axis,
+            items,
+        }
+    }
+}
+impl<T> NonFiniteCoordinateError<T> {
+    /// Returns the items given to the constructor which failed.
+    pub fn into_inner(self) -> T {
+        self.items
+    }
+}
+impl<T> std::fmt::Debug for NonFiniteCoordinateError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NonFiniteCoordinateError")
+            .field("index", &self.index)
+            .field("axis", &self.axis)
+            .finish_non_exhaustive()
+    }
+}
+impl<T> std::fmt::Display for NonFiniteCoordinateError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "the coordinate of item {} on axis {} is not finite",
+            self.index, self.axis
+        )
+    }
+}
+impl<T> std::error::Error for NonFiniteCoordinateError<T> {}
+
+/// An error returned by `from_sorted*`, when the items are not in the order of a kd-tree.
+///
+/// [`KdTreeN::from_sorted_vec`] and [`KdTreeN::from_sorted_vec_by`] give the items back by [`NotKdSortedError::into_inner`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct NotKdSortedError<T = ()>(T);
+impl<T> NotKdSortedError<T> {
+    /// Returns the items given to the constructor which failed.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T> std::fmt::Debug for NotKdSortedError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NotKdSortedError").finish_non_exhaustive()
+    }
+}
+impl<T> std::fmt::Display for NotKdSortedError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the items are not sorted as a kd-tree")
+    }
+}
+impl<T> std::error::Error for NotKdSortedError<T> {}
+
+fn check_finite<T: KdPoint>(points: &[T]) -> Result<(), NonFiniteCoordinateError>
+where
+    T::Scalar: num_traits::Float,
+{
+    use num_traits::Float;
+    for (index, point) in points.iter().enumerate() {
+        for axis in 0..T::dim() {
+            if !point.at(axis).is_finite() {
+                return Err(NonFiniteCoordinateError {
+                    index,
+                    axis,
+                    items: (),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 /// A slice of kd-tree.
 /// This type implements [`std::ops::Deref`] to `[T]`.
 /// This is an unsized type, meaning that it must always be used as a reference.
@@ -88,7 +265,7 @@ impl<T, N: Unsigned> std::ops::Deref for KdSliceN<T, N> {
 impl<T: Clone, N: Unsigned> std::borrow::ToOwned for KdSliceN<T, N> {
     type Owned = KdTreeN<T, N>;
     fn to_owned(&self) -> Self::Owned {
-        KdTreeN(PhantomData, self.1.to_vec())
+        KdTreeN(PhantomData, self.1.to_vec(), Vec::new())
     }
 }
 impl<T, N: Unsigned> KdSliceN<T, N> {
@@ -96,10 +273,55 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         &self.1
     }
 
+    /// A slice is always split in the round robin order, since it has nowhere to keep the axes.
+    pub(crate) fn split_axes(&self) -> Axes<'_> {
+        Axes::RoundRobin
+    }
+
     unsafe fn new_unchecked(items: &[T]) -> &Self {
         &*(items as *const _ as *const Self)
     }
 
+    /// Returns a kd-tree slice of the items, which have been sorted by [`KdSliceN::sort_by`] with the same `compare`.
+    /// Fails if the items are not in such an order.
+    pub fn from_sorted_by<F>(items: &[T], compare: F) -> Result<&Self, NotKdSortedError>
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        if is_kd_sorted_by(items, N::to_usize(), compare) {
+            Ok(unsafe { Self::new_unchecked(items) })
+        } else {
+            Err(NotKdSortedError(()))
+        }
+    }
+
+    /// Returns a kd-tree slice of the points, which have been sorted by e.g. [`KdSliceN::sort`].
+    /// Fails if the points are not in such an order.
+    /// # Example
+    /// ```
+    /// use kd_tree::KdSlice;
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// KdSlice::sort(&mut items);
+    /// let kdtree: &KdSlice<[i32; 3]> = KdSlice::from_sorted(&items).unwrap();
+    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
+    /// assert!(KdSlice::from_sorted(&[[3, 0, 0], [2, 0, 0], [1, 0, 0]]).is_err());
+    /// ```
+    pub fn from_sorted(points: &[T]) -> Result<&Self, NotKdSortedError>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        Self::from_sorted_by(points, compare_by_kd_point)
+    }
+
+    /// Returns a kd-tree slice of the items without checking their order.
+    ///
+    /// # Safety
+    /// The items must be in the order produced by [`KdSliceN::sort_by`] or the like.
+    /// Otherwise the queries on the returned slice return unspecified results.
+    pub unsafe fn from_sorted_unchecked(items: &[T]) -> &Self {
+        Self::new_unchecked(items)
+    }
+
     /// # Example
     /// ```
     /// struct Item {
@@ -161,6 +383,23 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         Self::sort_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
     }
 
+    /// Same as [`KdSliceN::sort_by_ordered_float`], but fails if any coordinate is NaN or infinite.
+    /// # Example
+    /// ```
+    /// use kd_tree::KdSlice;
+    /// let mut items: Vec<[f64; 3]> = vec![[1.0, 2.0, 3.0], [3.0, f64::NAN, 2.0]];
+    /// let err = KdSlice::try_sort_by_ordered_float(&mut items).unwrap_err();
+    /// assert_eq!((err.index, err.axis), (1, 1));
+    /// ```
+    pub fn try_sort_by_ordered_float(points: &mut [T]) -> Result<&Self, NonFiniteCoordinateError>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        check_finite(points)?;
+        Ok(Self::sort_by_ordered_float(points))
+    }
+
     /// # Example
     /// ```
     /// use kd_tree::KdSlice;
@@ -176,6 +415,57 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         Self::sort_by_key(points, |item, k| item.at(k))
     }
 
+    /// Same as [`KdSliceN::sort_by`], but sorts the subtrees in parallel.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdSlice3::par_sort_by(&mut items, |item1, item2, k| item1[k].cmp(&item2[k]));
+    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_sort_by<F>(items: &mut [T], compare: F) -> &Self
+    where
+        T: Send,
+        F: Fn(&T, &T, usize) -> Ordering + Copy + Send + Sync,
+    {
+        kd_par_sort_by(items, N::to_usize(), compare);
+        unsafe { Self::new_unchecked(items) }
+    }
+
+    /// Same as [`KdSliceN::sort_by_key`], but sorts the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_sort_by_key<Key: Ord, F>(items: &mut [T], kd_key: F) -> &Self
+    where
+        T: Send,
+        F: Fn(&T, usize) -> Key + Copy + Send + Sync,
+    {
+        Self::par_sort_by(items, move |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    /// Same as [`KdSliceN::sort_by_ordered_float`], but sorts the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_sort_by_ordered_float(points: &mut [T]) -> &Self
+    where
+        T: KdPoint<Dim = N> + Send,
+        T::Scalar: num_traits::Float,
+    {
+        Self::par_sort_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+    }
+
+    /// Same as [`KdSliceN::sort`], but sorts the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_sort(points: &mut [T]) -> &Self
+    where
+        T: KdPoint<Dim = N> + Send,
+        T::Scalar: Ord,
+    {
+        Self::par_sort_by_key(points, |item, k| item.at(k))
+    }
+}
+
+impl_kd_queries! {
     /// Returns the nearest item from the input point. Returns `None` if `self.is_empty()`.
     /// # Example
     /// ```
@@ -196,11 +486,11 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         &self,
         query: &Q,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Option<ItemAndDistance<T, Q::Scalar>> {
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
         if self.is_empty() {
             None
         } else {
-            Some(kd_nearest_by(self.items(), query, coord))
+            Some(kd_nearest_by(self.items(), self.split_axes(), query, coord))
         }
     }
 
@@ -214,14 +504,14 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
     pub fn nearest(
         &self,
         query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
-    ) -> Option<ItemAndDistance<T, T::Scalar>>
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
     {
         if self.is_empty() {
             None
         } else {
-            Some(kd_nearest(self.items(), query))
+            Some(kd_nearest(self.items(), self.split_axes(), query))
         }
     }
 
@@ -267,8 +557,8 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         query: &Q,
         num: usize,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Vec<ItemAndDistance<T, Q::Scalar>> {
-        kd_nearests_by(self.items(), query, num, coord)
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        kd_nearests_by(self.items(), self.split_axes(), query, num, coord)
     }
 
     /// Returns kNN(k nearest neighbors) from the input point.
@@ -285,178 +575,1197 @@ impl<T, N: Unsigned> KdSliceN<T, N> {
         &self,
         query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
         num: usize,
-    ) -> Vec<ItemAndDistance<T, T::Scalar>>
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
     {
-        kd_nearests(self.items(), query, num)
+        kd_nearests(self.items(), self.split_axes(), query, num)
     }
 
-    pub fn within_by_cmp(&self, compare: impl Fn(&T, usize) -> Ordering + Copy) -> Vec<&T> {
-        kd_within_by_cmp(&self, N::to_usize(), compare)
-    }
-
-    pub fn within_by<Q: KdPoint<Dim = N>>(
+    /// Same as [`KdSliceN::nearests_by`], but the result is in arbitrary order.
+    /// This saves the final sort when `num` is large.
+    pub fn nearests_unsorted_by<Q: KdPoint<Dim = N>>(
         &self,
-        query: &[Q; 2],
+        query: &Q,
+        num: usize,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Vec<&T> {
-        assert!((0..Q::dim()).all(|k| query[0].at(k) <= query[1].at(k)));
-        self.within_by_cmp(|item, k| {
-            let a = coord(item, k);
-            if a < query[0].at(k) {
-                Ordering::Less
-            } else if a > query[1].at(k) {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
-            }
-        })
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        use num_traits::One;
+        kd_nearests_unsorted_approx_by(
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            Q::Scalar::one(),
+            usize::MAX,
+            None,
+            |_| true,
+            coord,
+        )
     }
 
-    /// search points within a rectangular region
-    pub fn within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> Vec<&T>
+    /// Same as [`KdSliceN::nearests`], but the result is in arbitrary order.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let mut nearests = kdtree.nearests_unsorted(&[3, 1, 2], 2);
+    /// nearests.sort_by_key(|nearest| nearest.squared_distance);
+    /// assert_eq!(nearests[0].item, &[3, 1, 2]);
+    /// assert_eq!(nearests[1].item, &[3, 2, 2]);
+    /// ```
+    pub fn nearests_unsorted(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
     {
-        self.within_by(query, |item, k| item.at(k))
+        self.nearests_unsorted_by(query, num, |item, k| item.at(k))
     }
 
-    pub fn within_radius_by<Q: KdPoint<Dim = N>>(
-        &self,
+    /// Same as [`KdSliceN::nearests_by`], but writes the result into `nearests` instead of allocating a new `Vec`.
+    /// The previous contents of `nearests` are discarded.
+    pub fn nearests_into_by<'a, Q: KdPoint<Dim = N>>(
+        &'a self,
         query: &Q,
-        radius: Q::Scalar,
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'a, T, Q::Scalar>>,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Vec<&T> {
-        let mut results = self.within_by_cmp(|item, k| {
-            let coord = coord(item, k);
-            if coord < query.at(k) - radius {
-                Ordering::Less
-            } else if coord > query.at(k) + radius {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
-            }
-        });
-        results.retain(|item| {
-            let mut distance = <Q::Scalar as num_traits::Zero>::zero();
-            for k in 0..N::to_usize() {
-                let diff = coord(item, k) - query.at(k);
-                distance += diff * diff;
-            }
-            distance < radius * radius
-        });
-        results
+    ) {
+        use num_traits::One;
+        kd_nearests_approx_into_by(
+            nearests,
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            Q::Scalar::one(),
+            usize::MAX,
+            None,
+            |_| true,
+            coord,
+        );
+        sort_by_distance(nearests);
     }
 
-    /// search points within k-dimensional sphere
-    pub fn within_radius(
-        &self,
+    /// Same as [`KdSliceN::nearests`], but writes the result into `nearests` instead of allocating a new `Vec`.
+    /// The previous contents of `nearests` are discarded.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let mut nearests = Vec::new();
+    /// for query in &[[3, 1, 2], [1, 2, 3]] {
+    ///     kdtree.nearests_into(query, 2, &mut nearests);
+    ///     assert_eq!(nearests.len(), 2);
+    ///     assert_eq!(nearests[0].item, query);
+    /// }
+    /// ```
+    pub fn nearests_into<'a>(
+        &'a self,
         query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
-        radius: T::Scalar,
-    ) -> Vec<&T>
-    where
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'a, T, T::Scalar>>,
+    ) where
         T: KdPoint<Dim = N>,
     {
-        self.within_radius_by(query, radius, |item, k| item.at(k))
+        self.nearests_into_by(query, num, nearests, |item, k| item.at(k))
     }
-}
 
-/// An owned kd-tree.
-/// This type implements [`std::ops::Deref`] to [`KdSlice`].
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct KdTreeN<T, N: Unsigned>(PhantomData<N>, Vec<T>);
-pub type KdTree<T> = KdTreeN<T, <T as KdPoint>::Dim>;
-impl<T, N: Unsigned> std::ops::Deref for KdTreeN<T, N> {
-    type Target = KdSliceN<T, N>;
-    fn deref(&self) -> &Self::Target {
-        unsafe { KdSliceN::new_unchecked(&self.1) }
-    }
-}
-impl<T, N: Unsigned> AsRef<KdSliceN<T, N>> for KdTreeN<T, N> {
-    fn as_ref(&self) -> &KdSliceN<T, N> {
-        self
-    }
-}
-impl<T, N: Unsigned> std::borrow::Borrow<KdSliceN<T, N>> for KdTreeN<T, N> {
-    fn borrow(&self) -> &KdSliceN<T, N> {
-        self
-    }
-}
-impl<T, N: Unsigned> Into<Vec<T>> for KdTreeN<T, N> {
-    fn into(self) -> Vec<T> {
-        self.1
-    }
-}
-impl<T, N: Unsigned> KdTreeN<T, N> {
-    pub fn into_vec(self) -> Vec<T> {
-        self.1
+    /// Returns the nearest item from the input point in terms of the given metric.
+    /// Returns `None` if `self.is_empty()`.
+    pub fn nearest_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(kd_nearest_with_metric_by(
+                self.items(),
+                self.split_axes(),
+                query,
+                metric,
+                coord,
+            ))
+        }
     }
 
+    /// Returns the nearest item from the input point in terms of the given metric.
+    /// Returns `None` if `self.is_empty()`.
     /// # Example
     /// ```
-    /// struct Item {
-    ///     point: [i32; 3],
-    ///     id: usize,
-    /// }
-    /// let kdtree = kd_tree::KdTree3::build_by(
-    ///     vec![
-    ///         Item { point: [1, 2, 3], id: 111 },
-    ///         Item { point: [3, 1, 2], id: 222 },
-    ///         Item { point: [2, 3, 1], id: 333 },
-    ///     ],
-    ///     |item1, item2, k| item1.point[k].cmp(&item2.point[k])
-    /// );
-    /// assert_eq!(kdtree.nearest_by(&[3, 1, 2], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let found = kdtree.nearest_with_metric(&[3, 2], &kd_tree::Chebyshev).unwrap();
+    /// assert_eq!(found.item, &[2, 2]);
+    /// assert_eq!(found.squared_distance, 1);
     /// ```
-    pub fn build_by<F>(mut items: Vec<T>, compare: F) -> Self
+    pub fn nearest_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        metric: &M,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
     where
-        F: Fn(&T, &T, usize) -> Ordering + Copy,
+        T: KdPoint<Dim = N>,
     {
-        kd_sort_by(&mut items, N::to_usize(), compare);
-        Self(PhantomData, items)
+        self.nearest_with_metric_by(query, metric, |item, k| item.at(k))
     }
 
-    /// # Example
-    /// ```
-    /// struct Item {
-    ///     point: [f64; 3],
-    ///     id: usize,
-    /// }
-    /// let kdtree = kd_tree::KdTree3::build_by_key(
-    ///     vec![
-    ///         Item { point: [1.0, 2.0, 3.0], id: 111 },
-    ///         Item { point: [3.0, 1.0, 2.0], id: 222 },
-    ///         Item { point: [2.0, 3.0, 1.0], id: 333 },
-    ///     ],
-    ///     |item, k| ordered_float::OrderedFloat(item.point[k])
-    /// );
-    /// assert_eq!(kdtree.nearest_by(&[3.1, 0.9, 2.1], |item, k| item.point[k]).unwrap().item.id, 222);
-    /// ```
-    pub fn build_by_key<Key, F>(items: Vec<T>, kd_key: F) -> Self
-    where
-        Key: Ord,
-        F: Fn(&T, usize) -> Key + Copy,
-    {
-        Self::build_by(items, |item1, item2, k| {
-            kd_key(item1, k).cmp(&kd_key(item2, k))
-        })
+    /// Returns kNN(k nearest neighbors) from the input point in terms of the given metric.
+    pub fn nearests_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        num: usize,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        kd_nearests_with_metric_by(self.items(), self.split_axes(), query, num, metric, coord)
     }
 
+    /// Returns kNN(k nearest neighbors) from the input point in terms of the given metric.
     /// # Example
     /// ```
-    /// use kd_tree::KdTree;
-    /// let kdtree: KdTree<[f64; 3]> = KdTree::build_by_ordered_float(vec![
-    ///     [1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]
-    /// ]);
-    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let nearests = kdtree.nearests_with_metric(&[3, 2], 2, &kd_tree::Manhattan);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &[2, 2]);
+    /// assert_eq!(nearests[1].item, &[3, 0]);
     /// ```
-    pub fn build_by_ordered_float(points: Vec<T>) -> Self
+    pub fn nearests_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
-        T::Scalar: num_traits::Float,
     {
-        Self::build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+        self.nearests_with_metric_by(query, num, metric, |item, k| item.at(k))
+    }
+
+    /// Returns kNN(k nearest neighbors) within `max_radius` from the input point.
+    /// Fewer than `num` items are returned if there are not enough items within the radius.
+    pub fn nearests_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        max_radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        use num_traits::One;
+        kd_nearests_approx_by(
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            Q::Scalar::one(),
+            usize::MAX,
+            Some(max_radius * max_radius),
+            |_| true,
+            coord,
+        )
+    }
+
+    /// Returns kNN(k nearest neighbors) within `max_radius` from the input point.
+    /// Fewer than `num` items are returned if there are not enough items within the radius.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let nearests = kdtree.nearests_within_radius(&[3, 1, 2], 3, 2);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &[3, 1, 2]);
+    /// assert_eq!(nearests[1].item, &[3, 2, 2]);
+    /// ```
+    pub fn nearests_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        max_radius: T::Scalar,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_within_radius_by(query, num, max_radius, |item, k| item.at(k))
+    }
+
+    /// Returns the nearest item which satisfies the predicate. Returns `None` if there is no such item.
+    /// The rejected items are still used to prune the search, but never returned.
+    pub fn nearest_filtered_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        predicate: impl Fn(&T) -> bool + Copy,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        self.nearests_filtered_by(query, 1, predicate, coord).pop()
+    }
+
+    /// Returns the nearest item which satisfies the predicate. Returns `None` if there is no such item.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let found = kdtree.nearest_filtered(&[3, 1, 2], |item| item != &[3, 1, 2]).unwrap();
+    /// assert_eq!(found.item, &[2, 3, 1]);
+    /// ```
+    pub fn nearest_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_filtered_by(query, predicate, |item, k| item.at(k))
+    }
+
+    /// Returns kNN(k nearest neighbors) among the items which satisfy the predicate.
+    /// The rejected items are still used to prune the search, but never returned.
+    pub fn nearests_filtered_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        predicate: impl Fn(&T) -> bool + Copy,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        use num_traits::One;
+        kd_nearests_approx_by(
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            Q::Scalar::one(),
+            usize::MAX,
+            None,
+            predicate,
+            coord,
+        )
+    }
+
+    /// Returns kNN(k nearest neighbors) among the items which satisfy the predicate.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<([i32; 2], &str)> = vec![
+    ///     ([0, 0], "a"),
+    ///     ([1, 0], "a"),
+    ///     ([2, 0], "b"),
+    ///     ([3, 0], "b"),
+    /// ];
+    /// let kdmap = kd_tree::KdMapSlice::sort(&mut items);
+    /// let nearests = kdmap.nearests_filtered(&[0, 0], 2, |item| item.1 != "a");
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &([2, 0], "b"));
+    /// assert_eq!(nearests[1].item, &([3, 0], "b"));
+    /// ```
+    pub fn nearests_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        predicate: impl Fn(&T) -> bool + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_filtered_by(query, num, predicate, |item, k| item.at(k))
+    }
+
+    /// Returns an iterator which yields items in the order of distance from the input point.
+    pub fn nearest_iter_by<'a, Q: KdPoint<Dim = N>, F>(
+        &'a self,
+        query: &'a Q,
+        coord: F,
+    ) -> NearestIter<'a, T, Q, F>
+    where
+        F: Fn(&T, usize) -> Q::Scalar + Copy,
+    {
+        NearestIter::new(self.items(), self.split_axes(), query, coord)
+    }
+
+    /// Returns an iterator which yields items in the order of distance from the input point.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let found: Vec<_> = kdtree
+    ///     .nearest_iter(&[3, 1, 2])
+    ///     .take_while(|found| found.squared_distance < 5)
+    ///     .map(|found| found.item)
+    ///     .collect();
+    /// assert_eq!(found, vec![&[3, 1, 2], &[3, 2, 2]]);
+    /// ```
+    pub fn nearest_iter<'a, Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &'a self,
+        query: &'a Q,
+    ) -> NearestIter<'a, T, Q, impl Fn(&T, usize) -> T::Scalar + Copy>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_iter_by(query, |item, k| item.at(k))
+    }
+
+    /// Returns an approximate nearest item from the input point. Returns `None` if `self.is_empty()`.
+    /// The distance to the returned item is at most `1 + eps` times the distance to the true nearest item.
+    pub fn nearest_approx_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        eps: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        if self.is_empty() {
+            None
+        } else {
+            let factor = approx_factor(eps);
+            Some(kd_nearest_approx_by(
+                self.items(),
+                |k| query.at(k),
+                Q::dim(),
+                self.split_axes(),
+                &SquaredEuclidean,
+                factor,
+                usize::MAX,
+                coord,
+            ))
+        }
+    }
+
+    /// Returns an approximate nearest item from the input point. Returns `None` if `self.is_empty()`.
+    /// The distance to the returned item is at most `1 + eps` times the distance to the true nearest item.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[f64; 3]> = vec![[1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]];
+    /// let kdtree = kd_tree::KdSlice::sort_by_ordered_float(&mut items);
+    /// let found = kdtree.nearest_approx(&[3.1, 0.9, 2.1], 0.1).unwrap();
+    /// assert_eq!(found.item, &[3.0, 1.0, 2.0]);
+    /// ```
+    pub fn nearest_approx(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        eps: T::Scalar,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_approx_by(query, eps, |item, k| item.at(k))
+    }
+
+    /// Returns approximate kNN(k nearest neighbors) from the input point.
+    /// The distance to the i-th returned item is at most `1 + eps` times the distance to the true i-th nearest item.
+    pub fn nearests_approx_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        eps: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        let factor = approx_factor(eps);
+        kd_nearests_approx_by(
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            factor,
+            usize::MAX,
+            None,
+            |_| true,
+            coord,
+        )
+    }
+
+    /// Returns approximate kNN(k nearest neighbors) from the input point.
+    /// The distance to the i-th returned item is at most `1 + eps` times the distance to the true i-th nearest item.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[f64; 3]> = vec![[1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]];
+    /// let kdtree = kd_tree::KdSlice::sort_by_ordered_float(&mut items);
+    /// let found = kdtree.nearests_approx(&[1.5, 2.5, 1.8], 2, 0.1);
+    /// assert_eq!(found.len(), 2);
+    /// assert_eq!(found[0].item, &[2.0, 3.0, 1.0]);
+    /// ```
+    pub fn nearests_approx(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        eps: T::Scalar,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_approx_by(query, num, eps, |item, k| item.at(k))
+    }
+
+    /// Returns the nearest item found by visiting at most `max_visits` nodes. Returns `None` if `self.is_empty()`.
+    pub fn nearest_with_budget_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        max_visits: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        use num_traits::One;
+        if self.is_empty() {
+            None
+        } else {
+            Some(kd_nearest_approx_by(
+                self.items(),
+                |k| query.at(k),
+                Q::dim(),
+                self.split_axes(),
+                &SquaredEuclidean,
+                Q::Scalar::one(),
+                max_visits,
+                coord,
+            ))
+        }
+    }
+
+    /// Returns the nearest item found by visiting at most `max_visits` nodes. Returns `None` if `self.is_empty()`.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// assert_eq!(kdtree.nearest_with_budget(&[3, 1, 2], 3).unwrap().item, &[3, 1, 2]);
+    /// ```
+    pub fn nearest_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        max_visits: usize,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_with_budget_by(query, max_visits, |item, k| item.at(k))
+    }
+
+    /// Returns kNN(k nearest neighbors) found by visiting at most `max_visits` nodes.
+    pub fn nearests_with_budget_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        max_visits: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        use num_traits::One;
+        kd_nearests_approx_by(
+            self.items(),
+            |k| query.at(k),
+            Q::dim(),
+            self.split_axes(),
+            num,
+            &SquaredEuclidean,
+            Q::Scalar::one(),
+            max_visits,
+            None,
+            |_| true,
+            coord,
+        )
+    }
+
+    /// Returns kNN(k nearest neighbors) found by visiting at most `max_visits` nodes.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let nearests = kdtree.nearests_with_budget(&[3, 1, 2], 2, 4);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &[3, 1, 2]);
+    /// ```
+    pub fn nearests_with_budget(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        max_visits: usize,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_with_budget_by(query, num, max_visits, |item, k| item.at(k))
+    }
+
+    pub fn within_by_cmp(&self, compare: impl Fn(&T, usize) -> Ordering + Copy) -> Vec<&T> {
+        kd_within_by_cmp(self, N::to_usize(), self.split_axes(), compare)
+    }
+
+    /// Calls `f` for each item in the region given by `compare`, without allocating.
+    pub fn for_each_within_by_cmp<'a>(
+        &'a self,
+        compare: impl Fn(&T, usize) -> Ordering + Copy,
+        mut f: impl FnMut(&'a T),
+    ) {
+        kd_for_each_within_by_cmp(
+            self,
+            N::to_usize(),
+            self.split_axes(),
+            compare,
+            |item, _| f(item),
+        )
+    }
+
+    pub fn within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        let mut results = Vec::new();
+        self.within_into_by(query, &mut results, coord);
+        results
+    }
+
+    /// search points within a rectangular region
+    pub fn within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_by(query, |item, k| item.at(k))
+    }
+
+    /// Same as [`KdSliceN::within_by`], but writes the result into `results` instead of allocating a new `Vec`.
+    /// The previous contents of `results` are discarded.
+    pub fn within_into_by<'a, Q: KdPoint<Dim = N>>(
+        &'a self,
+        query: &[Q; 2],
+        results: &mut Vec<&'a T>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) {
+        results.clear();
+        self.for_each_within_by(query, coord, |item| results.push(item));
+    }
+
+    /// Same as [`KdSliceN::within`], but writes the result into `results` instead of allocating a new `Vec`.
+    /// The previous contents of `results` are discarded.
+    pub fn within_into<'a>(
+        &'a self,
+        query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2],
+        results: &mut Vec<&'a T>,
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_into_by(query, results, |item, k| item.at(k))
+    }
+
+    /// Calls `f` for each item within a rectangular region, without allocating.
+    pub fn for_each_within_by<'a, Q: KdPoint<Dim = N>>(
+        &'a self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        f: impl FnMut(&'a T),
+    ) {
+        assert!((0..Q::dim()).all(|k| query[0].at(k) <= query[1].at(k)));
+        self.for_each_within_by_cmp(
+            |item, k| {
+                let a = coord(item, k);
+                if a < query[0].at(k) {
+                    Ordering::Less
+                } else if a > query[1].at(k) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            },
+            f,
+        )
+    }
+
+    /// Calls `f` for each item within a rectangular region, without allocating.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let mut count = 0;
+    /// kdtree.for_each_within(&[[1, 0], [3, 3]], |_| count += 1);
+    /// assert_eq!(count, 2);
+    /// ```
+    pub fn for_each_within<'a>(
+        &'a self,
+        query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2],
+        f: impl FnMut(&'a T),
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.for_each_within_by(query, |item, k| item.at(k), f)
+    }
+
+    pub fn within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        self.within_distance_with_metric_by(query, radius * radius, &SquaredEuclidean, coord)
+    }
+
+    /// Same as [`KdSliceN::within_radius_by`], but writes the result into `results` instead of allocating a new `Vec`.
+    /// The previous contents of `results` are discarded.
+    pub fn within_radius_into_by<'a, Q: KdPoint<Dim = N>>(
+        &'a self,
+        query: &Q,
+        radius: Q::Scalar,
+        results: &mut Vec<&'a T>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) {
+        results.clear();
+        self.for_each_within_radius_by(query, radius, coord, |item| results.push(item));
+    }
+
+    /// Same as [`KdSliceN::within_radius`], but writes the result into `results` instead of allocating a new `Vec`.
+    /// The previous contents of `results` are discarded.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let mut found = Vec::new();
+    /// kdtree.within_radius_into(&[3, 1], 2, &mut found);
+    /// assert_eq!(found.len(), 2);
+    /// kdtree.within_radius_into(&[5, 4], 2, &mut found);
+    /// assert_eq!(found, vec![&[5, 5]]);
+    /// ```
+    pub fn within_radius_into<'a>(
+        &'a self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        results: &mut Vec<&'a T>,
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_into_by(query, radius, results, |item, k| item.at(k))
+    }
+
+    /// Calls `f` for each item within a sphere, without allocating.
+    pub fn for_each_within_radius_by<'a, Q: KdPoint<Dim = N>>(
+        &'a self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        f: impl FnMut(&'a T),
+    ) {
+        self.for_each_within_distance_with_metric_by(
+            query,
+            radius * radius,
+            &SquaredEuclidean,
+            coord,
+            f,
+        )
+    }
+
+    /// Calls `f` for each item within a sphere, without allocating.
+    pub fn for_each_within_radius<'a>(
+        &'a self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        f: impl FnMut(&'a T),
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.for_each_within_radius_by(query, radius, |item, k| item.at(k), f)
+    }
+
+    /// Counts the items within a rectangular region, without collecting them.
+    /// A subtree whose cell is inside the region is counted at once.
+    pub fn count_within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> usize {
+        assert!((0..Q::dim()).all(|k| query[0].at(k) <= query[1].at(k)));
+        let dim = Q::dim();
+        kd_count_within_by_cmp(
+            self,
+            dim,
+            self.split_axes(),
+            |item: &T, k| {
+                let a = coord(item, k);
+                if a < query[0].at(k) {
+                    Ordering::Less
+                } else if a > query[1].at(k) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            },
+            |_, _| true,
+            |bounds| {
+                (0..dim).all(|k| {
+                    bounds[k].is_some_and(|(min, _)| query[0].at(k) <= coord(min, k))
+                        && bounds[dim + k].is_some_and(|(max, _)| coord(max, k) <= query[1].at(k))
+                })
+            },
+        )
+    }
+
+    /// Counts the items within a rectangular region, without collecting them.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// assert_eq!(kdtree.count_within(&[[1, 0], [3, 3]]), 2);
+    /// ```
+    pub fn count_within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> usize
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.count_within_by(query, |item, k| item.at(k))
+    }
+
+    /// Counts the items within a sphere, without collecting them.
+    /// A subtree whose cell is inside the sphere is counted at once.
+    pub fn count_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> usize {
+        let dim = Q::dim();
+        let squared_radius = radius * radius;
+        kd_count_within_by_cmp(
+            self,
+            dim,
+            self.split_axes(),
+            |item: &T, k| radius_compare(query.at(k) - coord(item, k), squared_radius),
+            |item, _| nearest::distance(query, item, &SquaredEuclidean, coord) < squared_radius,
+            |bounds| {
+                let mut squared_distance = <Q::Scalar as num_traits::Zero>::zero();
+                for k in 0..dim {
+                    let (min, max) = match (bounds[k], bounds[dim + k]) {
+                        (Some((min, _)), Some((max, _))) => (coord(min, k), coord(max, k)),
+                        _ => return false,
+                    };
+                    let x = query.at(k);
+                    let diff = if x - min > max - x { x - min } else { max - x };
+                    squared_distance += diff * diff;
+                }
+                squared_distance < squared_radius
+            },
+        )
+    }
+
+    /// Counts the items within a sphere, without collecting them.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// assert_eq!(kdtree.count_within_radius(&[3, 1], 2), 2);
+    /// ```
+    pub fn count_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> usize
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.count_within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    /// Returns whether any item is within a rectangular region, stopping at the first one found.
+    pub fn any_within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> bool {
+        assert!((0..Q::dim()).all(|k| query[0].at(k) <= query[1].at(k)));
+        kd_any_within_by_cmp(
+            self,
+            Q::dim(),
+            self.split_axes(),
+            |item: &T, k| {
+                let a = coord(item, k);
+                if a < query[0].at(k) {
+                    Ordering::Less
+                } else if a > query[1].at(k) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            },
+            |_, _| true,
+        )
+    }
+
+    /// Returns whether any item is within a rectangular region, stopping at the first one found.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// assert!(kdtree.any_within(&[[1, 0], [3, 3]]));
+    /// assert!(!kdtree.any_within(&[[3, 3], [4, 4]]));
+    /// ```
+    pub fn any_within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> bool
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.any_within_by(query, |item, k| item.at(k))
+    }
+
+    /// Returns whether any item is within a sphere, stopping at the first one found.
+    pub fn any_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> bool {
+        let squared_radius = radius * radius;
+        kd_any_within_by_cmp(
+            self,
+            Q::dim(),
+            self.split_axes(),
+            |item: &T, k| radius_compare(query.at(k) - coord(item, k), squared_radius),
+            |item, _| nearest::distance(query, item, &SquaredEuclidean, coord) < squared_radius,
+        )
+    }
+
+    /// Returns whether any item is within a sphere, stopping at the first one found.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// assert!(kdtree.any_within_radius(&[3, 1], 2));
+    /// assert!(!kdtree.any_within_radius(&[4, 3], 1));
+    /// ```
+    pub fn any_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> bool
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.any_within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    /// search points whose distance from the input point is less than `distance` in terms of the given metric.
+    /// `distance` is in the same units as [`Metric::distance`].
+    pub fn within_distance_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        distance: Q::Scalar,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        let mut results = Vec::new();
+        self.for_each_within_distance_with_metric_by(query, distance, metric, coord, |item| {
+            results.push(item)
+        });
+        results
+    }
+
+    fn for_each_within_distance_with_metric_by<'a, Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &'a self,
+        query: &Q,
+        distance: Q::Scalar,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        mut f: impl FnMut(&'a T),
+    ) {
+        self.for_each_within_by_cmp(
+            |item, k| {
+                let diff = query.at(k) - coord(item, k);
+                if metric.axis_distance(k, diff) < distance {
+                    Ordering::Equal
+                } else if diff > <Q::Scalar as num_traits::Zero>::zero() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            },
+            |item| {
+                if nearest::distance(query, item, metric, coord) < distance {
+                    f(item)
+                }
+            },
+        )
+    }
+
+    /// search points whose distance from the input point is less than `distance` in terms of the given metric.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2], [5, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let found = kdtree.within_distance_with_metric(&[3, 2], 3, &kd_tree::Manhattan);
+    /// assert_eq!(found.len(), 2);
+    /// assert!(found.contains(&&[3, 0]));
+    /// assert!(found.contains(&&[2, 2]));
+    /// ```
+    pub fn within_distance_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        distance: T::Scalar,
+        metric: &M,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_distance_with_metric_by(query, distance, metric, |item, k| item.at(k))
+    }
+
+    /// search points within k-dimensional sphere
+    pub fn within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    pub fn within_radius_with_distance_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        options: WithinRadiusOptions,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        let squared_radius = radius * radius;
+        let mut results: Vec<_> = self
+            .within_by_cmp(|item, k| {
+                let diff = query.at(k) - coord(item, k);
+                if diff * diff <= squared_radius {
+                    Ordering::Equal
+                } else if diff > <Q::Scalar as num_traits::Zero>::zero() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .into_iter()
+            .map(|item| ItemAndDistance {
+                item,
+                squared_distance: nearest::distance(query, item, &SquaredEuclidean, coord),
+            })
+            .filter(|found| {
+                if options.inclusive {
+                    found.squared_distance <= squared_radius
+                } else {
+                    found.squared_distance < squared_radius
+                }
+            })
+            .collect();
+        if options.sorted {
+            results.sort_by(|a, b| {
+                a.squared_distance
+                    .partial_cmp(&b.squared_distance)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        results
+    }
+
+    /// search points within k-dimensional sphere, along with their squared distances from the input point.
+    /// # Example
+    /// ```
+    /// use kd_tree::WithinRadiusOptions;
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let options = WithinRadiusOptions { sorted: true, inclusive: true };
+    /// let found = kdtree.within_radius_with_distance(&[3, 1, 2], 1, options);
+    /// assert_eq!(found.len(), 2);
+    /// assert_eq!(found[0].item, &[3, 1, 2]);
+    /// assert_eq!(found[1].item, &[3, 2, 2]);
+    /// assert_eq!(found[1].squared_distance, 1);
+    /// ```
+    pub fn within_radius_with_distance(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        options: WithinRadiusOptions,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_with_distance_by(query, radius, options, |item, k| item.at(k))
+    }
+}
+
+/// An owned kd-tree.
+/// This type implements [`std::ops::Deref`] to [`KdSlice`].
+///
+/// A tree built by [`KdTreeN::build_with_strategy_by`] or the like also keeps the split axis of each node.
+/// Its own queries read the axes, but the [`KdSliceN`] it derefs to splits the nodes in the round robin order,
+/// so such a tree must be queried directly rather than through `&KdSliceN`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdTreeN<T, N: Unsigned>(PhantomData<N>, Vec<T>, Vec<u16>);
+pub type KdTree<T> = KdTreeN<T, <T as KdPoint>::Dim>;
+impl<T, N: Unsigned> Default for KdTreeN<T, N> {
+    fn default() -> Self {
+        Self(PhantomData, Vec::new(), Vec::new())
+    }
+}
+impl<T, N: Unsigned> std::ops::Deref for KdTreeN<T, N> {
+    type Target = KdSliceN<T, N>;
+    fn deref(&self) -> &Self::Target {
+        unsafe { KdSliceN::new_unchecked(&self.1) }
+    }
+}
+impl<T, N: Unsigned> AsRef<KdSliceN<T, N>> for KdTreeN<T, N> {
+    fn as_ref(&self) -> &KdSliceN<T, N> {
+        self
+    }
+}
+impl<T, N: Unsigned> std::borrow::Borrow<KdSliceN<T, N>> for KdTreeN<T, N> {
+    fn borrow(&self) -> &KdSliceN<T, N> {
+        self
+    }
+}
+impl<T, N: Unsigned> From<KdTreeN<T, N>> for Vec<T> {
+    fn from(kdtree: KdTreeN<T, N>) -> Self {
+        kdtree.1
+    }
+}
+impl<T, N: Unsigned> KdTreeN<T, N> {
+    pub fn into_vec(self) -> Vec<T> {
+        self.1
+    }
+
+    pub(crate) fn split_axes(&self) -> Axes<'_> {
+        Axes::new(&self.2)
+    }
+
+    /// Returns a kd-tree of the items, which have been sorted by [`KdTreeN::build_by`] with the same `compare`.
+    /// Fails if the items are not in such an order, and then the error gives the items back.
+    pub fn from_sorted_vec_by<F>(
+        items: Vec<T>,
+        compare: F,
+    ) -> Result<Self, NotKdSortedError<Vec<T>>>
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        if is_kd_sorted_by(&items, N::to_usize(), compare) {
+            Ok(Self(PhantomData, items, Vec::new()))
+        } else {
+            Err(NotKdSortedError(items))
+        }
+    }
+
+    /// Returns a kd-tree of the points, which have been sorted by e.g. [`KdTreeN::build`].
+    /// Fails if the points are not in such an order, and then the error gives the points back.
+    /// # Example
+    /// ```
+    /// use kd_tree::KdTree;
+    /// let items = KdTree::build(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]]).into_vec();
+    /// let kdtree: KdTree<[i32; 3]> = KdTree::from_sorted_vec(items).unwrap();
+    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
+    ///
+    /// let err = KdTree::<[i32; 3]>::from_sorted_vec(vec![[3, 1, 2], [1, 2, 3]]).unwrap_err();
+    /// assert_eq!(err.into_inner(), vec![[3, 1, 2], [1, 2, 3]]);
+    /// ```
+    pub fn from_sorted_vec(points: Vec<T>) -> Result<Self, NotKdSortedError<Vec<T>>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        Self::from_sorted_vec_by(points, compare_by_kd_point)
+    }
+
+    /// Returns a kd-tree of the items without checking their order.
+    ///
+    /// # Safety
+    /// The items must be in the order produced by [`KdTreeN::build_by`] or the like.
+    /// Otherwise the queries on the returned tree return unspecified results.
+    pub unsafe fn from_sorted_vec_unchecked(items: Vec<T>) -> Self {
+        Self(PhantomData, items, Vec::new())
+    }
+
+    /// # Example
+    /// ```
+    /// struct Item {
+    ///     point: [i32; 3],
+    ///     id: usize,
+    /// }
+    /// let kdtree = kd_tree::KdTree3::build_by(
+    ///     vec![
+    ///         Item { point: [1, 2, 3], id: 111 },
+    ///         Item { point: [3, 1, 2], id: 222 },
+    ///         Item { point: [2, 3, 1], id: 333 },
+    ///     ],
+    ///     |item1, item2, k| item1.point[k].cmp(&item2.point[k])
+    /// );
+    /// assert_eq!(kdtree.nearest_by(&[3, 1, 2], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// ```
+    pub fn build_by<F>(mut items: Vec<T>, compare: F) -> Self
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        kd_sort_by(&mut items, N::to_usize(), compare);
+        Self(PhantomData, items, Vec::new())
+    }
+
+    /// # Example
+    /// ```
+    /// struct Item {
+    ///     point: [f64; 3],
+    ///     id: usize,
+    /// }
+    /// let kdtree = kd_tree::KdTree3::build_by_key(
+    ///     vec![
+    ///         Item { point: [1.0, 2.0, 3.0], id: 111 },
+    ///         Item { point: [3.0, 1.0, 2.0], id: 222 },
+    ///         Item { point: [2.0, 3.0, 1.0], id: 333 },
+    ///     ],
+    ///     |item, k| ordered_float::OrderedFloat(item.point[k])
+    /// );
+    /// assert_eq!(kdtree.nearest_by(&[3.1, 0.9, 2.1], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// ```
+    pub fn build_by_key<Key, F>(items: Vec<T>, kd_key: F) -> Self
+    where
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy,
+    {
+        Self::build_by(items, |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    /// # Example
+    /// ```
+    /// use kd_tree::KdTree;
+    /// let kdtree: KdTree<[f64; 3]> = KdTree::build_by_ordered_float(vec![
+    ///     [1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]
+    /// ]);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+    /// ```
+    pub fn build_by_ordered_float(points: Vec<T>) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        Self::build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+    }
+
+    /// Same as [`KdTreeN::build_by_ordered_float`], but fails if any coordinate is NaN or infinite,
+    /// and then the error gives the points back.
+    /// # Example
+    /// ```
+    /// use kd_tree::KdTree;
+    /// let result = KdTree::try_build_by_ordered_float(vec![[1.0, 2.0], [f64::INFINITY, 1.0]]);
+    /// let err = result.unwrap_err();
+    /// assert_eq!(err.to_string(), "the coordinate of item 1 on axis 0 is not finite");
+    /// assert_eq!(err.into_inner(), vec![[1.0, 2.0], [f64::INFINITY, 1.0]]);
+    /// ```
+    pub fn try_build_by_ordered_float(
+        points: Vec<T>,
+    ) -> Result<Self, NonFiniteCoordinateError<Vec<T>>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        match check_finite(&points) {
+            Ok(()) => Ok(Self::build_by_ordered_float(points)),
+            Err(err) => Err(err.with_items(points)),
+        }
     }
 
     /// # Example
@@ -465,133 +1774,722 @@ impl<T, N: Unsigned> KdTreeN<T, N> {
     /// let kdtree: KdTree<[i32; 3]> = KdTree::build(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]]);
     /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &[3, 1, 2]);
     /// ```
-    pub fn build(points: Vec<T>) -> Self
+    pub fn build(points: Vec<T>) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Ord,
+    {
+        Self::build_by_key(points, |item, k| item.at(k))
+    }
+
+    fn sort_with_strategy<Scalar>(
+        mut items: Vec<T>,
+        strategy: SplitStrategy,
+        compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Self
+    where
+        Scalar: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        let axes = kd_sort_with_strategy_by(&mut items, N::to_usize(), strategy, compare, coord);
+        Self(PhantomData, items, axes)
+    }
+
+    /// Builds a kd-tree whose nodes are split along the axes chosen by `strategy`, still at the median.
+    ///
+    /// Like [`KdBucketTreeN::build_with_strategy_by`], this takes the coordinates instead of a comparator,
+    /// since the spread of the coordinates chooses the axes. Incomparable coordinates such as NaN are regarded as equal.
+    /// The split axis of each node is kept in the tree, and every query of the tree reads it.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`], which is only supported by [`KdBucketTreeN`].
+    /// # Example
+    /// ```
+    /// use kd_tree::SplitStrategy;
+    /// struct Item {
+    ///     point: [f64; 3],
+    ///     id: usize,
+    /// }
+    /// let kdtree = kd_tree::KdTree3::build_with_strategy_by(
+    ///     vec![
+    ///         Item { point: [1.0, 2.0, 0.0], id: 111 },
+    ///         Item { point: [3.0, 1.0, 0.1], id: 222 },
+    ///         Item { point: [2.0, 3.0, 0.0], id: 333 },
+    ///     ],
+    ///     SplitStrategy::MaxSpread,
+    ///     |item, k| item.point[k],
+    /// );
+    /// assert_eq!(kdtree.nearest_by(&[3.1, 0.9, 0.0], |item, k| item.point[k]).unwrap().item.id, 222);
+    /// ```
+    pub fn build_with_strategy_by<Scalar>(
+        items: Vec<T>,
+        strategy: SplitStrategy,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Self
+    where
+        Scalar: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        Self::sort_with_strategy(
+            items,
+            strategy,
+            |item1, item2, k| {
+                coord(item1, k)
+                    .partial_cmp(&coord(item2, k))
+                    .unwrap_or(Ordering::Equal)
+            },
+            coord,
+        )
+    }
+
+    /// Same as [`KdTreeN::build_by_ordered_float`], but splits the nodes along the axes chosen by `strategy`.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`], which is only supported by [`KdBucketTreeN`].
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdTree, SplitStrategy};
+    /// let points = vec![[1.0, 2.0, 0.0], [3.0, 1.0, 0.1], [2.0, 3.0, 0.0], [4.0, 4.0, 0.1]];
+    /// let kdtree = KdTree::build_with_strategy_by_ordered_float(points, SplitStrategy::MaxSpread);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 0.0]).unwrap().item, &[3.0, 1.0, 0.1]);
+    /// ```
+    pub fn build_with_strategy_by_ordered_float(points: Vec<T>, strategy: SplitStrategy) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        Self::sort_with_strategy(
+            points,
+            strategy,
+            |item1, item2, k| {
+                ordered_float::OrderedFloat(item1.at(k))
+                    .cmp(&ordered_float::OrderedFloat(item2.at(k)))
+            },
+            |item, k| item.at(k),
+        )
+    }
+
+    /// Same as [`KdTreeN::build`], but splits the nodes along the axes chosen by `strategy`.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`], which is only supported by [`KdBucketTreeN`].
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdTree, SplitStrategy};
+    /// let points = vec![[1, 2, 0], [3, 1, 0], [2, 3, 0], [4, 4, 1]];
+    /// let kdtree = KdTree::build_with_strategy(points, SplitStrategy::MaxSpread);
+    /// assert_eq!(kdtree.nearest(&[3, 1, 1]).unwrap().item, &[3, 1, 0]);
+    /// ```
+    pub fn build_with_strategy(points: Vec<T>, strategy: SplitStrategy) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Ord,
+    {
+        Self::sort_with_strategy(
+            points,
+            strategy,
+            |item1, item2, k| item1.at(k).cmp(&item2.at(k)),
+            |item, k| item.at(k),
+        )
+    }
+
+    /// Same as [`KdTreeN::build_by`], but builds the subtrees in parallel.
+    /// # Example
+    /// ```
+    /// use kd_tree::KdTree;
+    /// let kdtree: KdTree<[f64; 3]> = KdTree::par_build_by_ordered_float(vec![
+    ///     [1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]
+    /// ]);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 2.1]).unwrap().item, &[3.0, 1.0, 2.0]);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by<F>(mut items: Vec<T>, compare: F) -> Self
+    where
+        T: Send,
+        F: Fn(&T, &T, usize) -> Ordering + Copy + Send + Sync,
+    {
+        kd_par_sort_by(&mut items, N::to_usize(), compare);
+        Self(PhantomData, items, Vec::new())
+    }
+
+    /// Same as [`KdTreeN::build_by_key`], but builds the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by_key<Key, F>(items: Vec<T>, kd_key: F) -> Self
+    where
+        T: Send,
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy + Send + Sync,
+    {
+        Self::par_build_by(items, move |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    /// Same as [`KdTreeN::build_by_ordered_float`], but builds the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by_ordered_float(points: Vec<T>) -> Self
+    where
+        T: KdPoint<Dim = N> + Send,
+        T::Scalar: num_traits::Float,
+    {
+        Self::par_build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+    }
+
+    /// Same as [`KdTreeN::build`], but builds the subtrees in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_build(points: Vec<T>) -> Self
+    where
+        T: KdPoint<Dim = N> + Send,
+        T::Scalar: Ord,
+    {
+        Self::par_build_by_key(points, |item, k| item.at(k))
+    }
+}
+
+/// This type refers a slice of items, `[T]`, and contains kd-tree of indices to the items, `KdTree<usize, N>`.
+/// Unlike [`KdSliceN::sort`], [`KdIndexTreeN::build`] doesn't sort input items.
+/// ```
+/// let items = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+/// let kdtree = kd_tree::KdIndexTree::build(&items);
+/// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &1); // nearest() returns an index of items.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdIndexTreeN<'a, T, N: Unsigned> {
+    source: &'a [T],
+    kdtree: KdTreeN<usize, N>,
+}
+pub type KdIndexTree<'a, T> = KdIndexTreeN<'a, T, <T as KdPoint>::Dim>;
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    pub fn source(&self) -> &'a [T] {
+        self.source
+    }
+
+    pub fn indices(&self) -> &KdTreeN<usize, N> {
+        &self.kdtree
+    }
+
+    pub fn item(&self, i: usize) -> &'a T {
+        &self.source[i]
+    }
+
+    pub fn build_by<F>(source: &'a [T], compare: F) -> Self
+    where
+        F: Fn(&T, &T, usize) -> Ordering + Copy,
+    {
+        Self {
+            source,
+            kdtree: KdTreeN::build_by((0..source.len()).collect(), |i1, i2, k| {
+                compare(&source[*i1], &source[*i2], k)
+            }),
+        }
+    }
+
+    pub fn build_by_key<Key, F>(source: &'a [T], kd_key: F) -> Self
+    where
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy,
+    {
+        Self::build_by(source, |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    pub fn build_by_ordered_float(points: &'a [T]) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        Self::build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+    }
+
+    pub fn try_build_by_ordered_float(points: &'a [T]) -> Result<Self, NonFiniteCoordinateError>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        check_finite(points)?;
+        Ok(Self::build_by_ordered_float(points))
+    }
+
+    pub fn build(points: &'a [T]) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Ord,
+    {
+        Self::build_by_key(points, |item, k| item.at(k))
+    }
+
+    /// Same as [`KdTreeN::build_with_strategy_by`], but builds a kd-tree of indices to the source.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`].
+    pub fn build_with_strategy_by<Scalar>(
+        source: &'a [T],
+        strategy: SplitStrategy,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Self
+    where
+        Scalar: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        Self {
+            source,
+            kdtree: KdTreeN::build_with_strategy_by(
+                (0..source.len()).collect(),
+                strategy,
+                |&i, k| coord(&source[i], k),
+            ),
+        }
+    }
+
+    /// Same as [`KdTreeN::build_with_strategy_by_ordered_float`], but builds a kd-tree of indices to the points.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`].
+    /// # Example
+    /// ```
+    /// use kd_tree::{KdIndexTree, SplitStrategy};
+    /// let points = vec![[1.0, 2.0, 0.0], [3.0, 1.0, 0.1], [2.0, 3.0, 0.0], [4.0, 4.0, 0.1]];
+    /// let kdtree = KdIndexTree::build_with_strategy_by_ordered_float(&points, SplitStrategy::MaxSpread);
+    /// assert_eq!(kdtree.nearest(&[3.1, 0.9, 0.0]).unwrap().item, &1);
+    /// ```
+    pub fn build_with_strategy_by_ordered_float(points: &'a [T], strategy: SplitStrategy) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: num_traits::Float,
+    {
+        Self {
+            source: points,
+            kdtree: KdTreeN::sort_with_strategy(
+                (0..points.len()).collect(),
+                strategy,
+                |&i1, &i2, k| {
+                    ordered_float::OrderedFloat(points[i1].at(k))
+                        .cmp(&ordered_float::OrderedFloat(points[i2].at(k)))
+                },
+                |&i, k| points[i].at(k),
+            ),
+        }
+    }
+
+    /// Same as [`KdTreeN::build_with_strategy`], but builds a kd-tree of indices to the points.
+    /// # Panics
+    /// Panics if `strategy` is [`SplitStrategy::SlidingMidpoint`].
+    pub fn build_with_strategy(points: &'a [T], strategy: SplitStrategy) -> Self
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Ord,
+    {
+        Self {
+            source: points,
+            kdtree: KdTreeN::sort_with_strategy(
+                (0..points.len()).collect(),
+                strategy,
+                |&i1, &i2, k| points[i1].at(k).cmp(&points[i2].at(k)),
+                |&i, k| points[i].at(k),
+            ),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by<F>(source: &'a [T], compare: F) -> Self
+    where
+        T: Sync,
+        F: Fn(&T, &T, usize) -> Ordering + Copy + Send + Sync,
+    {
+        Self {
+            source,
+            kdtree: KdTreeN::par_build_by((0..source.len()).collect(), |i1, i2, k| {
+                compare(&source[*i1], &source[*i2], k)
+            }),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by_key<Key, F>(source: &'a [T], kd_key: F) -> Self
+    where
+        T: Sync,
+        Key: Ord,
+        F: Fn(&T, usize) -> Key + Copy + Send + Sync,
+    {
+        Self::par_build_by(source, move |item1, item2, k| {
+            kd_key(item1, k).cmp(&kd_key(item2, k))
+        })
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_build_by_ordered_float(points: &'a [T]) -> Self
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: num_traits::Float,
+    {
+        Self::par_build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_build(points: &'a [T]) -> Self
+    where
+        T: KdPoint<Dim = N> + Sync,
+        T::Scalar: Ord,
+    {
+        Self::par_build_by_key(points, |item, k| item.at(k))
+    }
+
+    pub fn nearest_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearest_by(query, |&index, k| coord(&self.source[index], k))
+    }
+
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdIndexTree3::build(&items);
+    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &1);
+    /// ```
+    pub fn nearest(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_by(query, |item, k| item.at(k))
+    }
+
+    pub fn nearests_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_by(query, num, |&index, k| coord(&self.source[index], k))
+    }
+
+    /// Returns kNN(k nearest neighbors) from the input point.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&mut items);
+    /// let nearests = kdtree.nearests(&[3, 1, 2], 2);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &1);
+    /// assert_eq!(nearests[1].item, &3);
+    /// ```
+    pub fn nearests(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_by(query, num, |item, k| item.at(k))
+    }
+
+    pub fn nearests_unsorted_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_unsorted_by(query, num, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn nearests_unsorted(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_unsorted_by(query, num, |item, k| item.at(k))
+    }
+
+    pub fn nearests_into_by<'b, Q: KdPoint<Dim = N>>(
+        &'b self,
+        query: &Q,
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'b, usize, Q::Scalar>>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) {
+        self.kdtree
+            .nearests_into_by(query, num, nearests, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    pub fn nearests_into<'b>(
+        &'b self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        nearests: &mut Vec<ItemAndDistance<'b, usize, T::Scalar>>,
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_into_by(query, num, nearests, |item, k| item.at(k))
+    }
+
+    pub fn nearest_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearest_with_metric_by(query, metric, |&index, k| coord(&self.source[index], k))
+    }
+
+    /// # Example
+    /// ```
+    /// let items: Vec<[i32; 2]> = vec![[0, 0], [3, 0], [2, 2]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// assert_eq!(kdtree.nearest_with_metric(&[3, 2], &kd_tree::Chebyshev).unwrap().item, &2);
+    /// ```
+    pub fn nearest_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        metric: &M,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_with_metric_by(query, metric, |item, k| item.at(k))
+    }
+
+    pub fn nearests_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        num: usize,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_with_metric_by(query, num, metric, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    pub fn nearests_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        metric: &M,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_with_metric_by(query, num, metric, |item, k| item.at(k))
+    }
+
+    pub fn nearests_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        max_radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_within_radius_by(query, num, max_radius, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    /// # Example
+    /// ```
+    /// let items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// let nearests = kdtree.nearests_within_radius(&[3, 1, 2], 3, 2);
+    /// assert_eq!(nearests.len(), 2);
+    /// assert_eq!(nearests[0].item, &1);
+    /// assert_eq!(nearests[1].item, &3);
+    /// ```
+    pub fn nearests_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        max_radius: T::Scalar,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_within_radius_by(query, num, max_radius, |item, k| item.at(k))
+    }
+
+    pub fn nearest_filtered_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        predicate: impl Fn(&usize) -> bool + Copy,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearest_filtered_by(query, predicate, |&index, k| coord(&self.source[index], k))
+    }
+
+    /// The predicate takes an index of an item.
+    /// # Example
+    /// ```
+    /// let items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// assert_eq!(kdtree.nearest_filtered(&items[1], |&i| i != 1).unwrap().item, &2);
+    /// ```
+    pub fn nearest_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        predicate: impl Fn(&usize) -> bool + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
-        T::Scalar: Ord,
     {
-        Self::build_by_key(points, |item, k| item.at(k))
+        self.nearest_filtered_by(query, predicate, |item, k| item.at(k))
     }
-}
 
-/// This type refers a slice of items, `[T]`, and contains kd-tree of indices to the items, `KdTree<usize, N>`.
-/// Unlike [`KdSliceN::sort`], [`KdIndexTreeN::build`] doesn't sort input items.
-/// ```
-/// let items = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
-/// let kdtree = kd_tree::KdIndexTree::build(&items);
-/// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &1); // nearest() returns an index of items.
-/// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct KdIndexTreeN<'a, T, N: Unsigned> {
-    source: &'a [T],
-    kdtree: KdTreeN<usize, N>,
-}
-pub type KdIndexTree<'a, T> = KdIndexTreeN<'a, T, <T as KdPoint>::Dim>;
-impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
-    pub fn source(&self) -> &'a [T] {
-        self.source
+    pub fn nearests_filtered_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        predicate: impl Fn(&usize) -> bool + Copy,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_filtered_by(query, num, predicate, |&index, k| {
+                coord(&self.source[index], k)
+            })
     }
 
-    pub fn indices(&self) -> &KdSliceN<usize, N> {
-        &self.kdtree
+    /// The predicate takes an index of an item.
+    pub fn nearests_filtered(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        predicate: impl Fn(&usize) -> bool + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_filtered_by(query, num, predicate, |item, k| item.at(k))
     }
 
-    pub fn item(&self, i: usize) -> &'a T {
-        &self.source[i]
+    pub fn nearest_iter_by<'b, Q: KdPoint<Dim = N>>(
+        &'b self,
+        query: &'b Q,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy + 'b,
+    ) -> NearestIter<'b, usize, Q, impl Fn(&usize, usize) -> Q::Scalar + Copy + 'b> {
+        let source = self.source;
+        self.kdtree
+            .nearest_iter_by(query, move |&index, k| coord(&source[index], k))
     }
 
-    pub fn build_by<F>(source: &'a [T], compare: F) -> Self
+    /// # Example
+    /// ```
+    /// let items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// let found: Vec<_> = kdtree.nearest_iter(&[3, 1, 2]).map(|found| *found.item).collect();
+    /// assert_eq!(found, vec![1, 3, 2, 0]);
+    /// ```
+    pub fn nearest_iter<'b, Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &'b self,
+        query: &'b Q,
+    ) -> NearestIter<'b, usize, Q, impl Fn(&usize, usize) -> T::Scalar + Copy + 'b>
     where
-        F: Fn(&T, &T, usize) -> Ordering + Copy,
+        T: KdPoint<Dim = N>,
     {
-        Self {
-            source,
-            kdtree: KdTreeN::build_by((0..source.len()).collect(), |i1, i2, k| {
-                compare(&source[*i1], &source[*i2], k)
-            }),
-        }
+        self.nearest_iter_by(query, |item, k| item.at(k))
     }
 
-    pub fn build_by_key<Key, F>(source: &'a [T], kd_key: F) -> Self
-    where
-        Key: Ord,
-        F: Fn(&T, usize) -> Key + Copy,
-    {
-        Self::build_by(source, |item1, item2, k| {
-            kd_key(item1, k).cmp(&kd_key(item2, k))
-        })
+    pub fn nearest_approx_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        eps: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearest_approx_by(query, eps, |&index, k| coord(&self.source[index], k))
     }
 
-    pub fn build_by_ordered_float(points: &'a [T]) -> Self
+    /// # Example
+    /// ```
+    /// let items: Vec<[f64; 3]> = vec![[1.0, 2.0, 3.0], [3.0, 1.0, 2.0], [2.0, 3.0, 1.0]];
+    /// let kdtree = kd_tree::KdIndexTree::build_by_ordered_float(&items);
+    /// assert_eq!(kdtree.nearest_approx(&[3.1, 0.9, 2.1], 0.1).unwrap().item, &1);
+    /// ```
+    pub fn nearest_approx(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        eps: T::Scalar,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
-        T::Scalar: num_traits::Float,
     {
-        Self::build_by_key(points, |item, k| ordered_float::OrderedFloat(item.at(k)))
+        self.nearest_approx_by(query, eps, |item, k| item.at(k))
     }
 
-    pub fn build(points: &'a [T]) -> Self
+    pub fn nearests_approx_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        eps: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .nearests_approx_by(query, num, eps, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn nearests_approx(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        eps: T::Scalar,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
-        T::Scalar: Ord,
     {
-        Self::build_by_key(points, |item, k| item.at(k))
+        self.nearests_approx_by(query, num, eps, |item, k| item.at(k))
     }
 
-    pub fn nearest_by<Q: KdPoint<Dim = N>>(
+    pub fn nearest_with_budget_by<Q: KdPoint<Dim = N>>(
         &self,
         query: &Q,
+        max_visits: usize,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Option<ItemAndDistance<usize, Q::Scalar>> {
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
         self.kdtree
-            .nearest_by(query, |&index, k| coord(&self.source[index], k))
+            .nearest_with_budget_by(query, max_visits, |&index, k| coord(&self.source[index], k))
     }
 
-    /// # Example
-    /// ```
-    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
-    /// let kdtree = kd_tree::KdIndexTree3::build(&items);
-    /// assert_eq!(kdtree.nearest(&[3, 1, 2]).unwrap().item, &1);
-    /// ```
-    pub fn nearest(
+    pub fn nearest_with_budget(
         &self,
         query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
-    ) -> Option<ItemAndDistance<usize, T::Scalar>>
+        max_visits: usize,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
     {
-        self.nearest_by(query, |item, k| item.at(k))
+        self.nearest_with_budget_by(query, max_visits, |item, k| item.at(k))
     }
 
-    pub fn nearests_by<Q: KdPoint<Dim = N>>(
+    pub fn nearests_with_budget_by<Q: KdPoint<Dim = N>>(
         &self,
         query: &Q,
         num: usize,
+        max_visits: usize,
         coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
-    ) -> Vec<ItemAndDistance<usize, Q::Scalar>> {
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
         self.kdtree
-            .nearests_by(query, num, |&index, k| coord(&self.source[index], k))
+            .nearests_with_budget_by(query, num, max_visits, |&index, k| {
+                coord(&self.source[index], k)
+            })
     }
 
-    /// Returns kNN(k nearest neighbors) from the input point.
-    /// # Example
-    /// ```
-    /// let mut items: Vec<[i32; 3]> = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [3, 2, 2]];
-    /// let kdtree = kd_tree::KdIndexTree::build(&mut items);
-    /// let nearests = kdtree.nearests(&[3, 1, 2], 2);
-    /// assert_eq!(nearests.len(), 2);
-    /// assert_eq!(nearests[0].item, &1);
-    /// assert_eq!(nearests[1].item, &3);
-    /// ```
-    pub fn nearests(
+    pub fn nearests_with_budget(
         &self,
         query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
         num: usize,
-    ) -> Vec<ItemAndDistance<usize, T::Scalar>>
+        max_visits: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
     where
         T: KdPoint<Dim = N>,
     {
-        self.nearests_by(query, num, |item, k| item.at(k))
+        self.nearests_with_budget_by(query, num, max_visits, |item, k| item.at(k))
     }
 
     pub fn within_by_cmp(&self, compare: impl Fn(&T, usize) -> Ordering + Copy) -> Vec<&usize> {
@@ -635,6 +2533,240 @@ impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
     {
         self.within_radius_by(query, radius, |item, k| item.at(k))
     }
+
+    pub fn within_into_by<'b, Q: KdPoint<Dim = N>>(
+        &'b self,
+        query: &[Q; 2],
+        results: &mut Vec<&'b usize>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) {
+        self.kdtree
+            .within_into_by(query, results, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn within_into<'b>(
+        &'b self,
+        query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2],
+        results: &mut Vec<&'b usize>,
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_into_by(query, results, |item, k| item.at(k))
+    }
+
+    pub fn for_each_within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        f: impl FnMut(&usize),
+    ) {
+        self.kdtree
+            .for_each_within_by(query, |&index, k| coord(&self.source[index], k), f)
+    }
+
+    pub fn for_each_within(
+        &self,
+        query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2],
+        f: impl FnMut(&usize),
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.for_each_within_by(query, |item, k| item.at(k), f)
+    }
+
+    pub fn within_radius_into_by<'b, Q: KdPoint<Dim = N>>(
+        &'b self,
+        query: &Q,
+        radius: Q::Scalar,
+        results: &mut Vec<&'b usize>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) {
+        self.kdtree
+            .within_radius_into_by(query, radius, results, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    pub fn within_radius_into<'b>(
+        &'b self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        results: &mut Vec<&'b usize>,
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_into_by(query, radius, results, |item, k| item.at(k))
+    }
+
+    pub fn for_each_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+        f: impl FnMut(&usize),
+    ) {
+        self.kdtree.for_each_within_radius_by(
+            query,
+            radius,
+            |&index, k| coord(&self.source[index], k),
+            f,
+        )
+    }
+
+    pub fn for_each_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        f: impl FnMut(&usize),
+    ) where
+        T: KdPoint<Dim = N>,
+    {
+        self.for_each_within_radius_by(query, radius, |item, k| item.at(k), f)
+    }
+
+    pub fn count_within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> usize {
+        self.kdtree
+            .count_within_by(query, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn count_within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> usize
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.count_within_by(query, |item, k| item.at(k))
+    }
+
+    pub fn count_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> usize {
+        self.kdtree
+            .count_within_radius_by(query, radius, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn count_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> usize
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.count_within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    pub fn any_within_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &[Q; 2],
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> bool {
+        self.kdtree
+            .any_within_by(query, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn any_within(&self, query: &[impl KdPoint<Scalar = T::Scalar, Dim = N>; 2]) -> bool
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.any_within_by(query, |item, k| item.at(k))
+    }
+
+    pub fn any_within_radius_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> bool {
+        self.kdtree
+            .any_within_radius_by(query, radius, |&index, k| coord(&self.source[index], k))
+    }
+
+    pub fn any_within_radius(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+    ) -> bool
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.any_within_radius_by(query, radius, |item, k| item.at(k))
+    }
+
+    pub fn within_radius_with_distance_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        options: WithinRadiusOptions,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.kdtree
+            .within_radius_with_distance_by(query, radius, options, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    pub fn within_radius_with_distance(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        options: WithinRadiusOptions,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_with_distance_by(query, radius, options, |item, k| item.at(k))
+    }
+
+    pub fn within_distance_with_metric_by<Q: KdPoint<Dim = N>, M: Metric<Q::Scalar>>(
+        &self,
+        query: &Q,
+        distance: Q::Scalar,
+        metric: &M,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&usize> {
+        self.kdtree
+            .within_distance_with_metric_by(query, distance, metric, |&index, k| {
+                coord(&self.source[index], k)
+            })
+    }
+
+    pub fn within_distance_with_metric<M: Metric<T::Scalar>>(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        distance: T::Scalar,
+        metric: &M,
+    ) -> Vec<&usize>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_distance_with_metric_by(query, distance, metric, |item, k| item.at(k))
+    }
+}
+
+/// Compares an item with a sphere along an axis, where `diff` is the query minus the item.
+fn radius_compare<Scalar: num_traits::NumAssign + Copy + PartialOrd>(
+    diff: Scalar,
+    squared_radius: Scalar,
+) -> Ordering {
+    if diff * diff < squared_radius {
+        Ordering::Equal
+    } else if diff > Scalar::zero() {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+/// `(1 + eps)^2`, the factor applied to the split plane distance by the approximate queries.
+fn approx_factor<Scalar: num_traits::NumAssign + Copy>(eps: Scalar) -> Scalar {
+    let factor = Scalar::one() + eps;
+    factor * factor
 }
 
 macro_rules! define_kdtree_aliases {
@@ -644,26 +2776,43 @@ macro_rules! define_kdtree_aliases {
                 pub type [<KdSlice $dim>]<T> = KdSliceN<T, typenum::[<U $dim>]>;
                 pub type [<KdTree $dim>]<T> = KdTreeN<T, typenum::[<U $dim>]>;
                 pub type [<KdIndexTree $dim>]<'a, T> = KdIndexTreeN<'a, T, typenum::[<U $dim>]>;
+                pub type [<KdDynamicTree $dim>]<T> = KdDynamicTreeN<T, typenum::[<U $dim>]>;
             }
         )*
     };
 }
 define_kdtree_aliases!(1, 2, 3, 4, 5, 6, 7, 8);
 
-macro_rules! impl_kd_points {
-    ($($len:literal),*) => {
-        $(
-            paste::paste!{
-                impl<T: num_traits::NumAssign + Copy + PartialOrd> KdPoint for [T; $len] {
-                    type Scalar = T;
-                    type Dim = typenum::[<U $len>];
-                    fn at(&self, i: usize) -> T { self[i] }
-                }
-            }
-        )*
-    };
+/// Fixed-sized arrays are points of dimension `N`, for `N <= 1024`.
+///
+/// The limit is deliberate: the dimension is the `typenum` integer [`Const<N>`],
+/// and `typenum` converts const generics to its integers only up to 1024.
+/// A longer array can be wrapped in a newtype which names its dimension by `typenum` directly.
+/// # Example
+/// ```
+/// struct Feature([f32; 2048]);
+/// impl kd_tree::KdPoint for Feature {
+///     type Scalar = f32;
+///     type Dim = typenum::U2048;
+///     fn at(&self, k: usize) -> f32 { self.0[k] }
+/// }
+/// let kdtree: kd_tree::KdTree<Feature> = kd_tree::KdTree::build_by_ordered_float(vec![
+///     Feature([1.0; 2048]),
+///     Feature([2.0; 2048]),
+/// ]);
+/// assert_eq!(kdtree.nearest(&Feature([1.9; 2048])).unwrap().item.0[0], 2.0);
+/// ```
+impl<T: num_traits::NumAssign + Copy + PartialOrd, const N: usize> KdPoint for [T; N]
+where
+    typenum::Const<N>: typenum::ToUInt,
+    Const<N>: Unsigned,
+{
+    type Scalar = T;
+    type Dim = Const<N>;
+    fn at(&self, i: usize) -> T {
+        self[i]
+    }
 }
-impl_kd_points!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16);
 
 impl<P: KdPoint, T> KdPoint for (P, T) {
     type Scalar = P::Scalar;
@@ -0,0 +1,172 @@
+/// A distance function used by the `*_with_metric` queries.
+///
+/// The value returned by [`Metric::distance`] does not need to be the true distance,
+/// only a monotonic function of it (e.g. [`SquaredEuclidean`] skips the square root).
+/// Query results and radii are expressed in the same units.
+///
+/// # Example
+/// ```
+/// use kd_tree::{KdTree, Manhattan};
+/// let kdtree = KdTree::build(vec![[0, 0], [3, 0], [2, 2]]);
+/// let found = kdtree.nearest_with_metric(&[3, 2], &Manhattan).unwrap();
+/// assert_eq!(found.item, &[2, 2]);
+/// assert_eq!(found.squared_distance, 1);
+/// ```
+pub trait Metric<Scalar> {
+    /// Returns the distance between two `dim`-dimensional points,
+    /// where `diff(k)` is the difference of their coordinates along the axis `k`.
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar;
+
+    /// Returns a lower bound of the distance between two points
+    /// whose coordinates along `axis` differ by `diff`.
+    /// It must not decrease as the absolute value of `diff` increases.
+    fn axis_distance(&self, axis: usize, diff: Scalar) -> Scalar;
+}
+
+fn abs<Scalar: num_traits::Zero + PartialOrd + std::ops::Neg<Output = Scalar>>(
+    x: Scalar,
+) -> Scalar {
+    if x < Scalar::zero() {
+        -x
+    } else {
+        x
+    }
+}
+
+/// Squared euclidean distance, which is used by [`KdSliceN::nearest`](crate::KdSliceN::nearest) etc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SquaredEuclidean;
+impl<Scalar: num_traits::NumAssign + Copy> Metric<Scalar> for SquaredEuclidean {
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar {
+        let mut distance = Scalar::zero();
+        for k in 0..dim {
+            let diff = diff(k);
+            distance += diff * diff;
+        }
+        distance
+    }
+    fn axis_distance(&self, _: usize, diff: Scalar) -> Scalar {
+        diff * diff
+    }
+}
+
+/// Manhattan (L1) distance, i.e. the sum of the absolute differences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Manhattan;
+impl<Scalar> Metric<Scalar> for Manhattan
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd + std::ops::Neg<Output = Scalar>,
+{
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar {
+        let mut distance = Scalar::zero();
+        for k in 0..dim {
+            distance += abs(diff(k));
+        }
+        distance
+    }
+    fn axis_distance(&self, _: usize, diff: Scalar) -> Scalar {
+        abs(diff)
+    }
+}
+
+/// Chebyshev (L∞) distance, i.e. the maximum of the absolute differences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Chebyshev;
+impl<Scalar> Metric<Scalar> for Chebyshev
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd + std::ops::Neg<Output = Scalar>,
+{
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar {
+        let mut distance = Scalar::zero();
+        for k in 0..dim {
+            let diff = abs(diff(k));
+            if diff > distance {
+                distance = diff;
+            }
+        }
+        distance
+    }
+    fn axis_distance(&self, _: usize, diff: Scalar) -> Scalar {
+        abs(diff)
+    }
+}
+
+/// Minkowski (Lp) distance raised to the power of `p`, i.e. the sum of `|diff|^p`.
+///
+/// # Example
+/// ```
+/// use kd_tree::{KdTree, Minkowski};
+/// let kdtree = KdTree::build_by_ordered_float(vec![[0.0, 0.0], [3.0, 0.0], [2.0, 2.0]]);
+/// let found = kdtree.nearest_with_metric(&[3.0, 2.0], &Minkowski::new(3.0)).unwrap();
+/// assert_eq!(found.item, &[2.0, 2.0]);
+/// assert_eq!(found.squared_distance, 1.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minkowski<Scalar> {
+    pub p: Scalar,
+}
+impl<Scalar: num_traits::Float> Minkowski<Scalar> {
+    /// # Panics
+    /// Panics if `p < 1`, because it is not a metric in that case.
+    pub fn new(p: Scalar) -> Self {
+        assert!(p >= Scalar::one());
+        Self { p }
+    }
+}
+impl<Scalar: num_traits::Float + num_traits::NumAssign> Metric<Scalar> for Minkowski<Scalar> {
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar {
+        let mut distance = Scalar::zero();
+        for k in 0..dim {
+            distance += diff(k).abs().powf(self.p);
+        }
+        distance
+    }
+    fn axis_distance(&self, _: usize, diff: Scalar) -> Scalar {
+        diff.abs().powf(self.p)
+    }
+}
+
+/// Squared euclidean distance with a weight for each axis, i.e. the sum of `weights[k] * diff * diff`.
+///
+/// The number of weights must equal the dimension of the queried points.
+///
+/// # Example
+/// ```
+/// use kd_tree::{KdTree, WeightedSquaredEuclidean};
+/// let kdtree = KdTree::build(vec![[0, 0], [3, 0], [2, 2]]);
+/// let metric = WeightedSquaredEuclidean::new(vec![1, 10]);
+/// assert_eq!(kdtree.nearest_with_metric(&[1, 0], &metric).unwrap().item, &[0, 0]);
+/// assert_eq!(kdtree.nearest_with_metric(&[1, 0], &metric).unwrap().squared_distance, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedSquaredEuclidean<Scalar> {
+    pub weights: Vec<Scalar>,
+}
+impl<Scalar: num_traits::Zero + PartialOrd> WeightedSquaredEuclidean<Scalar> {
+    /// # Panics
+    /// Panics if any weight is negative.
+    pub fn new(weights: Vec<Scalar>) -> Self {
+        assert!(weights.iter().all(|w| *w >= Scalar::zero()));
+        Self { weights }
+    }
+}
+impl<Scalar: num_traits::NumAssign + Copy> Metric<Scalar> for WeightedSquaredEuclidean<Scalar> {
+    /// # Panics
+    /// Panics if the number of weights is not `dim`.
+    fn distance(&self, dim: usize, diff: impl Fn(usize) -> Scalar) -> Scalar {
+        assert_eq!(
+            self.weights.len(),
+            dim,
+            "the number of weights does not match the dimension"
+        );
+        let mut distance = Scalar::zero();
+        for k in 0..dim {
+            let diff = diff(k);
+            distance += self.weights[k] * diff * diff;
+        }
+        distance
+    }
+    fn axis_distance(&self, axis: usize, diff: Scalar) -> Scalar {
+        self.weights[axis] * diff * diff
+    }
+}
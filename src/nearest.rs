@@ -1,78 +1,171 @@
-use crate::{ItemAndDistance, KdPoint};
+use crate::coords::CoordAt;
+use crate::sort::Axes;
+use crate::{ItemAndDistance, KdPoint, Metric, SquaredEuclidean};
 
 pub fn kd_nearest<'a, T: KdPoint>(
     kdtree: &'a [T],
+    axes: Axes<'_>,
     query: &impl KdPoint<Scalar = T::Scalar, Dim = T::Dim>,
 ) -> ItemAndDistance<'a, T, T::Scalar> {
-    kd_nearest_by(kdtree, query, |item, k| item.at(k))
+    kd_nearest_by(kdtree, axes, query, |item, k| item.at(k))
 }
 
 pub fn kd_nearest_by<'a, T, P: KdPoint>(
     kdtree: &'a [T],
+    axes: Axes<'_>,
     query: &P,
     get: impl Fn(&T, usize) -> P::Scalar + Copy,
 ) -> ItemAndDistance<'a, T, P::Scalar> {
-    fn distance_squared<P: KdPoint, T>(
-        p1: &P,
-        p2: &T,
-        get: impl Fn(&T, usize) -> P::Scalar,
-    ) -> P::Scalar {
-        let mut squared_distance = <P::Scalar as num_traits::Zero>::zero();
-        for i in 0..P::dim() {
-            let diff = p1.at(i) - get(p2, i);
-            squared_distance += diff * diff;
-        }
-        squared_distance
-    }
-    fn recurse<'a, T, Q: KdPoint>(
-        nearest: &mut ItemAndDistance<'a, T, Q::Scalar>,
+    kd_nearest_with_metric_by(kdtree, axes, query, &SquaredEuclidean, get)
+}
+
+pub fn kd_nearest_with_metric_by<'a, T, P: KdPoint, M: Metric<P::Scalar>>(
+    kdtree: &'a [T],
+    axes: Axes<'_>,
+    query: &P,
+    metric: &M,
+    get: impl Fn(&T, usize) -> P::Scalar + Copy,
+) -> ItemAndDistance<'a, T, P::Scalar> {
+    use num_traits::One;
+    kd_nearest_approx_by(
+        kdtree,
+        |k| query.at(k),
+        P::dim(),
+        axes,
+        metric,
+        P::Scalar::one(),
+        usize::MAX,
+        get,
+    )
+}
+
+/// A branch is skipped if the distance to its split plane multiplied by `factor`
+/// is not less than the current nearest distance.
+/// At most `max_visits` nodes are visited.
+/// The query is given by its coordinates `query(k)` and its dimension `dim`, so that the dimension can be given at runtime.
+/// The nodes are split along `axes`.
+#[allow(clippy::too_many_arguments)]
+pub fn kd_nearest_approx_by<'a, T, S, M: Metric<S>>(
+    kdtree: &'a [T],
+    query: impl Fn(usize) -> S + Copy,
+    dim: usize,
+    axes: Axes<'_>,
+    metric: &M,
+    factor: S,
+    max_visits: usize,
+    get: impl CoordAt<T, S>,
+) -> ItemAndDistance<'a, T, S>
+where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    #[allow(clippy::too_many_arguments)]
+    fn recurse<'a, T, S, M: Metric<S>>(
+        nearest: &mut ItemAndDistance<'a, T, S>,
         kdtree: &'a [T],
-        get: impl Fn(&T, usize) -> Q::Scalar + Copy,
-        query: &Q,
+        offset: usize,
+        get: impl CoordAt<T, S>,
+        query: impl Fn(usize) -> S + Copy,
+        dim: usize,
+        axes: Axes<'_>,
+        metric: &M,
+        factor: S,
+        visits: &mut usize,
         axis: usize,
-    ) {
+    ) where
+        S: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        if *visits == 0 {
+            return;
+        }
+        *visits -= 1;
         let mid_idx = kdtree.len() / 2;
         let item = &kdtree[mid_idx];
-        let squared_distance = distance_squared(query, item, get);
+        let squared_distance =
+            metric.distance(dim, |k| query(k) - get.at(offset + mid_idx, item, k));
         if squared_distance < nearest.squared_distance {
             nearest.item = item;
             nearest.squared_distance = squared_distance;
-            use num_traits::Zero;
             if nearest.squared_distance.is_zero() {
                 return;
             }
         }
-        let mid_pos = get(item, axis);
-        let [branch1, branch2] = if query.at(axis) < mid_pos {
-            [&kdtree[..mid_idx], &kdtree[mid_idx + 1..]]
+        let axis = axes.get(offset + mid_idx, axis);
+        let mid_pos = get.at(offset + mid_idx, item, axis);
+        let lower = (&kdtree[..mid_idx], offset);
+        let upper = (&kdtree[mid_idx + 1..], offset + mid_idx + 1);
+        let [branch1, branch2] = if query(axis) < mid_pos {
+            [lower, upper]
         } else {
-            [&kdtree[mid_idx + 1..], &kdtree[..mid_idx]]
+            [upper, lower]
         };
-        if !branch1.is_empty() {
-            recurse(nearest, branch1, get, query, (axis + 1) % Q::dim());
+        let next_axis = (axis + 1) % dim;
+        if !branch1.0.is_empty() {
+            recurse(
+                nearest, branch1.0, branch1.1, get, query, dim, axes, metric, factor, visits,
+                next_axis,
+            );
         }
-        if !branch2.is_empty() {
-            let diff = query.at(axis) - mid_pos;
-            if diff * diff < nearest.squared_distance {
-                recurse(nearest, branch2, get, query, (axis + 1) % Q::dim());
+        if !branch2.0.is_empty() {
+            let diff = query(axis) - mid_pos;
+            if metric.axis_distance(axis, diff) * factor < nearest.squared_distance {
+                recurse(
+                    nearest, branch2.0, branch2.1, get, query, dim, axes, metric, factor, visits,
+                    next_axis,
+                );
             }
         }
     }
     assert!(!kdtree.is_empty());
     let mut nearest = ItemAndDistance {
         item: &kdtree[0],
-        squared_distance: distance_squared(query, &kdtree[0], get),
+        squared_distance: metric.distance(dim, |k| query(k) - get.at(0, &kdtree[0], k)),
     };
-    recurse(&mut nearest, kdtree, get, query, 0);
+    let mut visits = max_visits;
+    recurse(
+        &mut nearest,
+        kdtree,
+        0,
+        get,
+        query,
+        dim,
+        axes,
+        metric,
+        factor,
+        &mut visits,
+        0,
+    );
     nearest
 }
 
+pub(crate) fn distance<P: KdPoint, T, M: Metric<P::Scalar>>(
+    query: &P,
+    item: &T,
+    metric: &M,
+    get: impl Fn(&T, usize) -> P::Scalar,
+) -> P::Scalar {
+    distance_by(|k| query.at(k), P::dim(), item, metric, get)
+}
+
+/// Same as [`distance`], but the query is given by its coordinates `query(k)` and its dimension `dim`.
+pub(crate) fn distance_by<T, S, M: Metric<S>>(
+    query: impl Fn(usize) -> S,
+    dim: usize,
+    item: &T,
+    metric: &M,
+    get: impl Fn(&T, usize) -> S,
+) -> S
+where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    metric.distance(dim, |k| query(k) - get(item, k))
+}
+
 #[allow(dead_code)]
 pub fn kd_nearest_with<T, Scalar>(
     kdtree: &[T],
     dim: usize,
     kd_difference: impl Fn(&T, usize) -> Scalar + Copy,
-) -> ItemAndDistance<T, Scalar>
+) -> ItemAndDistance<'_, T, Scalar>
 where
     Scalar: num_traits::NumAssign + Copy + PartialOrd,
 {
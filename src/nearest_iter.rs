@@ -0,0 +1,126 @@
+use crate::nearest::distance;
+use crate::sort::Axes;
+use crate::{ItemAndDistance, KdPoint, Metric, SquaredEuclidean};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// An iterator which yields items in the order of distance from the query point,
+/// returned by [`KdSliceN::nearest_iter`](crate::KdSliceN::nearest_iter) etc.
+///
+/// Subtrees are visited lazily in the best-first order, so that stopping the iteration early costs
+/// only as much as the items yielded so far.
+pub struct NearestIter<'a, T, Q: KdPoint, F> {
+    query: &'a Q,
+    axes: Axes<'a>,
+    coord: F,
+    heap: BinaryHeap<Candidate<'a, T, Q::Scalar>>,
+}
+
+impl<'a, T, Q: KdPoint, F> NearestIter<'a, T, Q, F>
+where
+    F: Fn(&T, usize) -> Q::Scalar + Copy,
+{
+    pub(crate) fn new(kdtree: &'a [T], axes: Axes<'a>, query: &'a Q, coord: F) -> Self {
+        let mut heap = BinaryHeap::new();
+        if !kdtree.is_empty() {
+            heap.push(Candidate {
+                distance: <Q::Scalar as num_traits::Zero>::zero(),
+                kind: Kind::Node(kdtree, 0, 0),
+            });
+        }
+        Self {
+            query,
+            axes,
+            coord,
+            heap,
+        }
+    }
+}
+
+impl<'a, T, Q: KdPoint, F> Iterator for NearestIter<'a, T, Q, F>
+where
+    F: Fn(&T, usize) -> Q::Scalar + Copy,
+{
+    type Item = ItemAndDistance<'a, T, Q::Scalar>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(candidate) = self.heap.pop() {
+            let (kdtree, offset, axis) = match candidate.kind {
+                Kind::Item(item) => {
+                    return Some(ItemAndDistance {
+                        item,
+                        squared_distance: candidate.distance,
+                    })
+                }
+                Kind::Node(kdtree, offset, axis) => (kdtree, offset, axis),
+            };
+            let mid_idx = kdtree.len() / 2;
+            let axis = self.axes.get(offset + mid_idx, axis);
+            let item = &kdtree[mid_idx];
+            self.heap.push(Candidate {
+                distance: distance(self.query, item, &SquaredEuclidean, self.coord),
+                kind: Kind::Item(item),
+            });
+            let mid_pos = (self.coord)(item, axis);
+            let lower = (&kdtree[..mid_idx], offset);
+            let upper = (&kdtree[mid_idx + 1..], offset + mid_idx + 1);
+            let [(branch1, offset1), (branch2, offset2)] = if self.query.at(axis) < mid_pos {
+                [lower, upper]
+            } else {
+                [upper, lower]
+            };
+            let next_axis = (axis + 1) % Q::dim();
+            if !branch1.is_empty() {
+                self.heap.push(Candidate {
+                    distance: candidate.distance,
+                    kind: Kind::Node(branch1, offset1, next_axis),
+                });
+            }
+            if !branch2.is_empty() {
+                let bound = SquaredEuclidean.axis_distance(axis, self.query.at(axis) - mid_pos);
+                self.heap.push(Candidate {
+                    distance: if bound > candidate.distance {
+                        bound
+                    } else {
+                        candidate.distance
+                    },
+                    kind: Kind::Node(branch2, offset2, next_axis),
+                });
+            }
+        }
+        None
+    }
+}
+
+enum Kind<'a, T> {
+    Item(&'a T),
+    /// A subtree, its position in the kd-tree, and its axis in the round robin order.
+    Node(&'a [T], usize, usize),
+}
+
+/// An entry of the priority queue.
+/// `distance` is the distance to the item, or a lower bound of the distances to the items in the subtree.
+struct Candidate<'a, T, Scalar> {
+    distance: Scalar,
+    kind: Kind<'a, T>,
+}
+
+impl<'a, T, Scalar: PartialOrd> Ord for Candidate<'a, T, Scalar> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that `BinaryHeap` pops the nearest one first.
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+impl<'a, T, Scalar: PartialOrd> PartialOrd for Candidate<'a, T, Scalar> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<'a, T, Scalar: PartialOrd> PartialEq for Candidate<'a, T, Scalar> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<'a, T, Scalar: PartialOrd> Eq for Candidate<'a, T, Scalar> {}
@@ -1,82 +1,344 @@
-use crate::{ItemAndDistance, KdPoint};
+use crate::coords::CoordAt;
+use crate::sort::Axes;
+use crate::{ItemAndDistance, KdPoint, Metric, SquaredEuclidean};
+use std::cmp::Ordering;
 
 pub fn kd_nearests<'a, T: KdPoint>(
     kdtree: &'a [T],
+    axes: Axes<'_>,
     query: &impl KdPoint<Scalar = T::Scalar, Dim = T::Dim>,
     num: usize,
 ) -> Vec<ItemAndDistance<'a, T, T::Scalar>> {
-    kd_nearests_by(kdtree, query, num, |item, k| item.at(k))
+    kd_nearests_by(kdtree, axes, query, num, |item, k| item.at(k))
 }
 
 pub fn kd_nearests_by<'a, T, P: KdPoint>(
     kdtree: &'a [T],
+    axes: Axes<'_>,
     query: &P,
     num: usize,
     get: impl Fn(&T, usize) -> P::Scalar + Copy,
 ) -> Vec<ItemAndDistance<'a, T, P::Scalar>> {
-    fn distance_squared<P: KdPoint, T>(
-        p1: &P,
-        p2: &T,
-        get: impl Fn(&T, usize) -> P::Scalar,
-    ) -> P::Scalar {
-        let mut squared_distance = <P::Scalar as num_traits::Zero>::zero();
-        for i in 0..P::dim() {
-            let diff = p1.at(i) - get(p2, i);
-            squared_distance += diff * diff;
-        }
-        squared_distance
-    }
-    fn recurse<'a, T, Q: KdPoint>(
-        nearests: &mut Vec<ItemAndDistance<'a, T, Q::Scalar>>,
+    kd_nearests_with_metric_by(kdtree, axes, query, num, &SquaredEuclidean, get)
+}
+
+pub fn kd_nearests_with_metric_by<'a, T, P: KdPoint, M: Metric<P::Scalar>>(
+    kdtree: &'a [T],
+    axes: Axes<'_>,
+    query: &P,
+    num: usize,
+    metric: &M,
+    get: impl Fn(&T, usize) -> P::Scalar + Copy,
+) -> Vec<ItemAndDistance<'a, T, P::Scalar>> {
+    use num_traits::One;
+    kd_nearests_approx_by(
+        kdtree,
+        |k| query.at(k),
+        P::dim(),
+        axes,
+        num,
+        metric,
+        P::Scalar::one(),
+        usize::MAX,
+        None,
+        |_| true,
+        get,
+    )
+}
+
+/// Same as [`kd_nearests_unsorted_approx_by`], but the result is sorted by the distance.
+#[allow(clippy::too_many_arguments)]
+pub fn kd_nearests_approx_by<'a, T, S, M: Metric<S>>(
+    kdtree: &'a [T],
+    query: impl Fn(usize) -> S + Copy,
+    dim: usize,
+    axes: Axes<'_>,
+    num: usize,
+    metric: &M,
+    factor: S,
+    max_visits: usize,
+    max_distance: Option<S>,
+    filter: impl Fn(&T) -> bool + Copy,
+    get: impl Fn(&T, usize) -> S + Copy,
+) -> Vec<ItemAndDistance<'a, T, S>>
+where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    let mut nearests = kd_nearests_unsorted_approx_by(
+        kdtree,
+        query,
+        dim,
+        axes,
+        num,
+        metric,
+        factor,
+        max_visits,
+        max_distance,
+        filter,
+        get,
+    );
+    sort_by_distance(&mut nearests);
+    nearests
+}
+
+/// A branch is skipped if the distance to its split plane multiplied by `factor`
+/// is not less than the current k-th nearest distance.
+/// At most `max_visits` nodes are visited,
+/// and only items whose distance is less than `max_distance` and which satisfy `filter` are returned.
+/// The query is given by its coordinates `query(k)` and its dimension `dim`, so that the dimension can be given at runtime.
+/// The nodes are split along `axes`, and the result is in arbitrary order.
+#[allow(clippy::too_many_arguments)]
+pub fn kd_nearests_unsorted_approx_by<'a, T, S, M: Metric<S>>(
+    kdtree: &'a [T],
+    query: impl Fn(usize) -> S + Copy,
+    dim: usize,
+    axes: Axes<'_>,
+    num: usize,
+    metric: &M,
+    factor: S,
+    max_visits: usize,
+    max_distance: Option<S>,
+    filter: impl Fn(&T) -> bool + Copy,
+    get: impl Fn(&T, usize) -> S + Copy,
+) -> Vec<ItemAndDistance<'a, T, S>>
+where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    let mut nearests = Vec::new();
+    kd_nearests_approx_into_by(
+        &mut nearests,
+        kdtree,
+        query,
+        dim,
+        axes,
+        num,
+        metric,
+        factor,
+        max_visits,
+        max_distance,
+        filter,
+        get,
+    );
+    nearests
+}
+
+/// Same as [`kd_nearests_unsorted_approx_by`], but writes the result into `nearests` reusing its allocation.
+#[allow(clippy::too_many_arguments)]
+pub fn kd_nearests_approx_into_by<'a, T, S, M: Metric<S>>(
+    nearests: &mut Vec<ItemAndDistance<'a, T, S>>,
+    kdtree: &'a [T],
+    query: impl Fn(usize) -> S + Copy,
+    dim: usize,
+    axes: Axes<'_>,
+    num: usize,
+    metric: &M,
+    factor: S,
+    max_visits: usize,
+    max_distance: Option<S>,
+    filter: impl Fn(&T) -> bool + Copy,
+    get: impl CoordAt<T, S>,
+) where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    #[allow(clippy::too_many_arguments)]
+    fn recurse<'a, T, S, M: Metric<S>>(
+        nearests: &mut NearestHeap<'a, T, S>,
         kdtree: &'a [T],
-        get: impl Fn(&T, usize) -> Q::Scalar + Copy,
-        query: &Q,
+        offset: usize,
+        get: impl CoordAt<T, S>,
+        query: impl Fn(usize) -> S + Copy,
+        dim: usize,
+        axes: Axes<'_>,
+        metric: &M,
+        factor: S,
+        visits: &mut usize,
+        max_distance: Option<S>,
+        filter: impl Fn(&T) -> bool + Copy,
         axis: usize,
-    ) {
+    ) where
+        S: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        if *visits == 0 {
+            return;
+        }
+        *visits -= 1;
         let mid_idx = kdtree.len() / 2;
         let item = &kdtree[mid_idx];
-        let squared_distance = distance_squared(query, item, get);
-        if nearests.len() < nearests.capacity()
-            || squared_distance < nearests.last().unwrap().squared_distance
-        {
-            if nearests.len() == nearests.capacity() {
-                nearests.pop();
-            }
-            let i = nearests
-                .binary_search_by(|item| {
-                    item.squared_distance
-                        .partial_cmp(&squared_distance)
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                })
-                .unwrap_or_else(|i| i);
-            nearests.insert(
-                i,
-                ItemAndDistance {
-                    item,
-                    squared_distance,
-                },
-            );
+        let squared_distance =
+            metric.distance(dim, |k| query(k) - get.at(offset + mid_idx, item, k));
+        if nearests.admits(squared_distance, max_distance) && filter(item) {
+            nearests.push(item, squared_distance);
         }
-        let mid_pos = get(item, axis);
-        let [branch1, branch2] = if query.at(axis) < mid_pos {
-            [&kdtree[..mid_idx], &kdtree[mid_idx + 1..]]
+        let axis = axes.get(offset + mid_idx, axis);
+        let mid_pos = get.at(offset + mid_idx, item, axis);
+        let lower = (&kdtree[..mid_idx], offset);
+        let upper = (&kdtree[mid_idx + 1..], offset + mid_idx + 1);
+        let [branch1, branch2] = if query(axis) < mid_pos {
+            [lower, upper]
         } else {
-            [&kdtree[mid_idx + 1..], &kdtree[..mid_idx]]
+            [upper, lower]
         };
-        if !branch1.is_empty() {
-            recurse(nearests, branch1, get, query, (axis + 1) % Q::dim());
+        let next_axis = (axis + 1) % dim;
+        if !branch1.0.is_empty() {
+            recurse(
+                nearests,
+                branch1.0,
+                branch1.1,
+                get,
+                query,
+                dim,
+                axes,
+                metric,
+                factor,
+                visits,
+                max_distance,
+                filter,
+                next_axis,
+            );
         }
-        if !branch2.is_empty() {
-            let diff = query.at(axis) - mid_pos;
-            if diff * diff < nearests.last().unwrap().squared_distance {
-                recurse(nearests, branch2, get, query, (axis + 1) % Q::dim());
+        if !branch2.0.is_empty() {
+            let diff = query(axis) - mid_pos;
+            let bound = metric.axis_distance(axis, diff) * factor;
+            if nearests.admits(bound, max_distance) {
+                recurse(
+                    nearests,
+                    branch2.0,
+                    branch2.1,
+                    get,
+                    query,
+                    dim,
+                    axes,
+                    metric,
+                    factor,
+                    visits,
+                    max_distance,
+                    filter,
+                    next_axis,
+                );
             }
         }
     }
+    let mut heap = NearestHeap::from_vec(std::mem::take(nearests), num);
     if num == 0 || kdtree.is_empty() {
-        return Vec::new();
+        *nearests = heap.into_vec();
+        return;
+    }
+    let mut visits = max_visits;
+    recurse(
+        &mut heap,
+        kdtree,
+        0,
+        get,
+        query,
+        dim,
+        axes,
+        metric,
+        factor,
+        &mut visits,
+        max_distance,
+        filter,
+        0,
+    );
+    *nearests = heap.into_vec();
+}
+
+/// Sorts the items by the distance in ascending order.
+pub fn sort_by_distance<T, Scalar: PartialOrd>(nearests: &mut [ItemAndDistance<T, Scalar>]) {
+    nearests.sort_unstable_by(|a, b| {
+        a.squared_distance
+            .partial_cmp(&b.squared_distance)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// A bounded max-heap which keeps the `num` nearest items pushed so far.
+/// A push costs O(log(num)), unlike the insertion to a sorted `Vec` which costs O(num).
+/// The heap is kept in a plain `Vec`, so that the buffer given to [`NearestHeap::from_vec`] is reused.
+pub struct NearestHeap<'a, T, Scalar> {
+    heap: Vec<ItemAndDistance<'a, T, Scalar>>,
+    num: usize,
+}
+
+impl<'a, T, Scalar: PartialOrd + Copy> NearestHeap<'a, T, Scalar> {
+    pub fn new(num: usize) -> Self {
+        Self::from_vec(Vec::with_capacity(num), num)
+    }
+
+    /// Reuses the allocation of `buffer`, whose contents are discarded.
+    pub fn from_vec(mut buffer: Vec<ItemAndDistance<'a, T, Scalar>>, num: usize) -> Self {
+        buffer.clear();
+        Self { heap: buffer, num }
+    }
+
+    /// Returns the distance which an item must be less than to be pushed,
+    /// i.e. the distance of the `num`-th nearest item if the heap is full, or `max_distance` otherwise.
+    pub fn threshold(&self, max_distance: Option<Scalar>) -> Option<Scalar> {
+        if self.heap.len() < self.num {
+            max_distance
+        } else {
+            self.heap.first().map(|farthest| farthest.squared_distance)
+        }
+    }
+
+    /// Returns whether an item at `squared_distance` is nearer than the [`threshold`](Self::threshold).
+    pub fn admits(&self, squared_distance: Scalar, max_distance: Option<Scalar>) -> bool {
+        match self.threshold(max_distance) {
+            Some(threshold) => squared_distance < threshold,
+            None => true,
+        }
+    }
+
+    /// Pushes an item, and drops the farthest one if the heap overflows.
+    pub fn push(&mut self, item: &'a T, squared_distance: Scalar) {
+        if self.num == 0 {
+            return;
+        }
+        let entry = ItemAndDistance {
+            item,
+            squared_distance,
+        };
+        if self.heap.len() < self.num {
+            self.heap.push(entry);
+            self.sift_up(self.heap.len() - 1);
+        } else {
+            self.heap[0] = entry;
+            self.sift_down(0);
+        }
+    }
+
+    /// Returns the items in arbitrary order, keeping the allocation of the heap.
+    pub fn into_vec(self) -> Vec<ItemAndDistance<'a, T, Scalar>> {
+        self.heap
+    }
+
+    fn farther(&self, i: usize, j: usize) -> bool {
+        self.heap[i].squared_distance > self.heap[j].squared_distance
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !self.farther(i, parent) {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut farthest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len() && self.farther(child, farthest) {
+                    farthest = child;
+                }
+            }
+            if farthest == i {
+                break;
+            }
+            self.heap.swap(i, farthest);
+            i = farthest;
+        }
     }
-    let mut nearests = Vec::with_capacity(num);
-    recurse(&mut nearests, kdtree, get, query, 0);
-    nearests
 }
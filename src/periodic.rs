@@ -0,0 +1,434 @@
+use crate::nearests::{sort_by_distance, NearestHeap};
+use crate::sort::Axes;
+use crate::within::kd_for_each_within_by_cmp;
+use crate::{radius_compare, ItemAndDistance, KdIndexTreeN, KdPoint};
+use std::cmp::Ordering;
+use typenum::Unsigned;
+
+/// Periodic boundary conditions, under which the domain wraps around like a torus.
+///
+/// The domain is `[0, periods[k])` along each axis `k`, and the items must lie in the domain:
+/// the distances to items outside the domain would be wrong, so the queries panic on them in debug builds.
+/// Queries outside the domain are wrapped into it,
+/// and the distances are measured to the nearest periodic image (the minimum image convention).
+/// # Example
+/// ```
+/// let mut items: Vec<[f64; 2]> = vec![[0.5, 5.0], [5.0, 5.0], [9.5, 5.0]];
+/// let kdtree = kd_tree::KdSlice::sort_by_ordered_float(&mut items);
+/// let periodic = kd_tree::Periodic::new(vec![10.0, 10.0]);
+/// let found = kdtree.nearests_periodic(&[9.9, 5.0], 2, &periodic);
+/// assert_eq!(found[0].item, &[9.5, 5.0]);
+/// assert_eq!(found[1].item, &[0.5, 5.0]); // across the boundary
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Periodic<Scalar> {
+    periods: Vec<Scalar>,
+}
+
+impl<Scalar: num_traits::NumAssign + Copy + PartialOrd> Periodic<Scalar> {
+    /// # Panics
+    /// Panics if any period is not positive.
+    pub fn new(periods: Vec<Scalar>) -> Self {
+        assert!(
+            periods.iter().all(|&period| period > Scalar::zero()),
+            "periods must be positive"
+        );
+        Self { periods }
+    }
+
+    pub fn periods(&self) -> &[Scalar] {
+        &self.periods
+    }
+
+    /// Wraps `x` into `[0, periods[k])`.
+    pub fn wrap(&self, k: usize, x: Scalar) -> Scalar {
+        let period = self.periods[k];
+        let x = x % period;
+        let x = if x < Scalar::zero() { x + period } else { x };
+        // `x + period` rounds to `period` if `x` is a tiny negative number.
+        if x < period {
+            x
+        } else {
+            Scalar::zero()
+        }
+    }
+
+    /// Returns the difference `a - b` along the axis `k` to the nearest image, where `a` and `b` are in the domain.
+    pub fn difference(&self, k: usize, a: Scalar, b: Scalar) -> Scalar {
+        let period = self.periods[k];
+        let diff = a - b;
+        let double = diff + diff;
+        if double > period {
+            diff - period
+        } else if double < Scalar::zero() - period {
+            diff + period
+        } else {
+            diff
+        }
+    }
+
+    /// Returns the squared distance between `a` and the nearest image of `b`, where `a` and `b` are in the domain.
+    pub fn squared_distance(
+        &self,
+        a: impl Fn(usize) -> Scalar,
+        b: impl Fn(usize) -> Scalar,
+    ) -> Scalar {
+        let mut squared_distance = Scalar::zero();
+        for k in 0..self.periods.len() {
+            let diff = self.difference(k, a(k), b(k));
+            squared_distance += diff * diff;
+        }
+        squared_distance
+    }
+
+    /// Returns the distance along the axis `k` from `x` to the nearest image of `[lo, hi]`,
+    /// where all of them are in `[0, periods[k]]`.
+    fn distance_to_range(&self, k: usize, x: Scalar, lo: Scalar, hi: Scalar) -> Scalar {
+        let period = self.periods[k];
+        let (direct, across) = if x < lo {
+            (lo - x, x + period - hi)
+        } else if x > hi {
+            (x - hi, lo + period - x)
+        } else {
+            return Scalar::zero();
+        };
+        if across < direct {
+            across
+        } else {
+            direct
+        }
+    }
+
+    /// Checks in debug builds that an item visited by a query is in the domain.
+    fn debug_check_item(&self, x: impl Fn(usize) -> Scalar) {
+        debug_assert!(
+            (0..self.periods.len()).all(|k| Scalar::zero() <= x(k) && x(k) < self.periods[k]),
+            "an item is outside the periodic domain"
+        );
+    }
+
+    fn check_dim(&self, dim: usize) {
+        assert_eq!(
+            self.periods.len(),
+            dim,
+            "the number of periods differs from the dimension"
+        );
+    }
+}
+
+/// Searches `num` nearest items of `query` under the periodic boundary conditions, in arbitrary order.
+///
+/// The cell of each subtree is bounded by the items splitting it, starting from the whole domain,
+/// and a subtree is skipped if the nearest image of its cell is not nearer than the current `num`-th nearest item.
+/// The nodes are split along `axes`.
+fn kd_nearests_periodic_by<'a, T, S>(
+    kdtree: &'a [T],
+    axes: Axes<'_>,
+    query: &[S],
+    num: usize,
+    periodic: &Periodic<S>,
+    coord: impl Fn(&T, usize) -> S + Copy,
+) -> Vec<ItemAndDistance<'a, T, S>>
+where
+    S: num_traits::NumAssign + Copy + PartialOrd,
+{
+    struct Context<'a, 'q, T, S, C> {
+        nearests: NearestHeap<'a, T, S>,
+        // `[min[0], .., min[dim - 1], max[0], .., max[dim - 1]]` of the current cell.
+        bounds: Vec<S>,
+        axes: Axes<'q>,
+        query: &'q [S],
+        periodic: &'q Periodic<S>,
+        coord: C,
+    }
+    impl<'a, T, S, C> Context<'a, '_, T, S, C>
+    where
+        S: num_traits::NumAssign + Copy + PartialOrd,
+        C: Fn(&T, usize) -> S,
+    {
+        fn cell_distance(&self) -> S {
+            let dim = self.query.len();
+            let mut squared_distance = S::zero();
+            for k in 0..dim {
+                let diff = self.periodic.distance_to_range(
+                    k,
+                    self.query[k],
+                    self.bounds[k],
+                    self.bounds[dim + k],
+                );
+                squared_distance += diff * diff;
+            }
+            squared_distance
+        }
+
+        fn recurse(&mut self, kdtree: &'a [T], offset: usize, axis: usize) {
+            let dim = self.query.len();
+            let mid = kdtree.len() / 2;
+            let axis = self.axes.get(offset + mid, axis);
+            let item = &kdtree[mid];
+            let coord = &self.coord;
+            self.periodic.debug_check_item(|k| coord(item, k));
+            let squared_distance = self
+                .periodic
+                .squared_distance(|k| self.query[k], |k| coord(item, k));
+            if self.nearests.admits(squared_distance, None) {
+                self.nearests.push(item, squared_distance);
+            }
+            let split = coord(item, axis);
+            // (subtree, its position, index of the bound replaced by the split)
+            let lower = (&kdtree[..mid], offset, dim + axis);
+            let upper = (&kdtree[mid + 1..], offset + mid + 1, axis);
+            let branches = if self.query[axis] < split {
+                [lower, upper]
+            } else {
+                [upper, lower]
+            };
+            for (branch, offset, bound) in branches {
+                if branch.is_empty() {
+                    continue;
+                }
+                let saved = std::mem::replace(&mut self.bounds[bound], split);
+                let cell_distance = self.cell_distance();
+                if self.nearests.admits(cell_distance, None) {
+                    self.recurse(branch, offset, (axis + 1) % dim);
+                }
+                self.bounds[bound] = saved;
+            }
+        }
+    }
+    if num == 0 || kdtree.is_empty() {
+        return Vec::new();
+    }
+    let mut bounds = vec![S::zero(); query.len()];
+    bounds.extend_from_slice(periodic.periods());
+    let mut context = Context {
+        nearests: NearestHeap::new(num),
+        bounds,
+        axes,
+        query,
+        periodic,
+        coord,
+    };
+    context.recurse(kdtree, 0, 0);
+    context.nearests.into_vec()
+}
+
+impl_kd_queries! {
+    /// Returns the nearest item from the input point under the periodic boundary conditions.
+    /// Returns `None` if `self.is_empty()`.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    pub fn nearest_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>> {
+        self.nearests_periodic_by(query, 1, periodic, coord).pop()
+    }
+
+    /// Returns the nearest item from the input point under the periodic boundary conditions.
+    /// Returns `None` if `self.is_empty()`.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[1, 5], [5, 5], [8, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let periodic = kd_tree::Periodic::new(vec![10, 10]);
+    /// let found = kdtree.nearest_periodic(&[0, 5], &periodic).unwrap();
+    /// assert_eq!(found.item, &[1, 5]);
+    /// let found = kdtree.nearest_periodic(&[-1, 5], &periodic).unwrap(); // wrapped to [9, 5]
+    /// assert_eq!(found.item, &[8, 5]);
+    /// ```
+    pub fn nearest_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_periodic_by(query, periodic, |item, k| item.at(k))
+    }
+
+    /// Returns `num` nearest items sorted by the distance under the periodic boundary conditions.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    pub fn nearests_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>> {
+        let dim = Q::dim();
+        periodic.check_dim(dim);
+        let query: Vec<_> = (0..dim).map(|k| periodic.wrap(k, query.at(k))).collect();
+        let mut nearests = kd_nearests_periodic_by(
+            self.items(),
+            self.split_axes(),
+            &query,
+            num,
+            periodic,
+            coord,
+        );
+        sort_by_distance(&mut nearests);
+        nearests
+    }
+
+    /// Returns `num` nearest items sorted by the distance under the periodic boundary conditions.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    pub fn nearests_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_periodic_by(query, num, periodic, |item, k| item.at(k))
+    }
+
+    /// search items within a sphere under the periodic boundary conditions.
+    /// Each item is returned once, even if the sphere overlaps its several images.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    pub fn within_radius_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T> {
+        let dim = Q::dim();
+        periodic.check_dim(dim);
+        let query: Vec<_> = (0..dim).map(|k| periodic.wrap(k, query.at(k))).collect();
+        let squared_radius = radius * radius;
+        // the axes along which the sphere crosses the boundary, where both sides of every split are searched.
+        let crossing: Vec<_> = (0..dim)
+            .map(|k| query[k] < radius || query[k] + radius > periodic.periods[k])
+            .collect();
+        let mut results = Vec::new();
+        kd_for_each_within_by_cmp(
+            self.items(),
+            dim,
+            self.split_axes(),
+            |item: &T, k| {
+                if crossing[k] {
+                    Ordering::Equal
+                } else {
+                    radius_compare(query[k] - coord(item, k), squared_radius)
+                }
+            },
+            |item, _| {
+                periodic.debug_check_item(|k| coord(item, k));
+                if periodic.squared_distance(|k| query[k], |k| coord(item, k)) < squared_radius {
+                    results.push(item);
+                }
+            },
+        );
+        results
+    }
+
+    /// search items within a sphere under the periodic boundary conditions.
+    /// # Panics
+    /// Panics if the number of periods differs from the dimension.
+    /// The items must lie in the domain of `periodic`, and in debug builds it panics if a visited item doesn't.
+    /// # Example
+    /// ```
+    /// let mut items: Vec<[i32; 2]> = vec![[1, 5], [5, 5], [8, 5]];
+    /// let kdtree = kd_tree::KdSlice::sort(&mut items);
+    /// let periodic = kd_tree::Periodic::new(vec![10, 10]);
+    /// assert_eq!(kdtree.within_radius_periodic(&[0, 5], 3, &periodic).len(), 2);
+    /// ```
+    pub fn within_radius_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_periodic_by(query, radius, periodic, |item, k| item.at(k))
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    pub fn nearest_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.indices()
+            .nearest_periodic_by(query, periodic, |&index, k| coord(self.item(index), k))
+    }
+
+    pub fn nearest_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearest_periodic_by(query, periodic, |item, k| item.at(k))
+    }
+
+    pub fn nearests_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        num: usize,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>> {
+        self.indices()
+            .nearests_periodic_by(query, num, periodic, |&index, k| coord(self.item(index), k))
+    }
+
+    pub fn nearests_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        num: usize,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.nearests_periodic_by(query, num, periodic, |item, k| item.at(k))
+    }
+
+    pub fn within_radius_periodic_by<Q: KdPoint<Dim = N>>(
+        &self,
+        query: &Q,
+        radius: Q::Scalar,
+        periodic: &Periodic<Q::Scalar>,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&usize> {
+        self.indices()
+            .within_radius_periodic_by(query, radius, periodic, |&index, k| {
+                coord(self.item(index), k)
+            })
+    }
+
+    pub fn within_radius_periodic(
+        &self,
+        query: &impl KdPoint<Scalar = T::Scalar, Dim = N>,
+        radius: T::Scalar,
+        periodic: &Periodic<T::Scalar>,
+    ) -> Vec<&usize>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_radius_periodic_by(query, radius, periodic, |item, k| item.at(k))
+    }
+}
@@ -0,0 +1,490 @@
+use crate::within::kd_for_each_within_region_by;
+use crate::{KdIndexTreeN, KdPoint};
+use num_traits::Float;
+use typenum::Unsigned;
+
+/// How a cell of a kd-tree overlaps a [`Region`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overlap {
+    /// The cell is entirely inside the region.
+    Inside,
+    /// The cell is entirely outside the region.
+    Outside,
+    /// The cell may be partially inside the region.
+    Partial,
+}
+
+/// A region of the space to search by [`KdSliceN::within_region`](crate::KdSliceN::within_region).
+///
+/// # Example
+/// ```
+/// use kd_tree::{Overlap, Region};
+/// // the points whose coordinates sum up to less than `0`.
+/// struct Negative;
+/// impl Region<i32> for Negative {
+///     fn classify(&self, min: &[Option<i32>], max: &[Option<i32>]) -> Overlap {
+///         let sum = |bounds: &[Option<i32>]| bounds.iter().copied().sum::<Option<i32>>();
+///         if sum(max).is_some_and(|sum| sum < 0) {
+///             Overlap::Inside
+///         } else if sum(min).is_some_and(|sum| sum >= 0) {
+///             Overlap::Outside
+///         } else {
+///             Overlap::Partial
+///         }
+///     }
+///     fn contains(&self, point: &[i32]) -> bool {
+///         point.iter().sum::<i32>() < 0
+///     }
+/// }
+/// let kdtree = kd_tree::KdTree::build(vec![[1, 2], [-3, 1], [-1, -1], [2, -3]]);
+/// assert_eq!(kdtree.within_region(&Negative).len(), 3);
+/// ```
+pub trait Region<Scalar> {
+    /// Classifies the axis-aligned box from `min` to `max`, where `None` means the box is unbounded.
+    ///
+    /// `Overlap::Partial` is always a correct answer;
+    /// the more precisely the boxes are classified, the more cells are pruned or taken at once.
+    fn classify(&self, min: &[Option<Scalar>], max: &[Option<Scalar>]) -> Overlap;
+
+    /// Returns `true` if the point is in the region.
+    fn contains(&self, point: &[Scalar]) -> bool;
+
+    /// Returns the dimension of the region, or `None` if it accepts any dimension.
+    /// The queries check that it matches the dimension of the kd-tree.
+    fn dim(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<Scalar, R: Region<Scalar> + ?Sized> Region<Scalar> for &R {
+    fn classify(&self, min: &[Option<Scalar>], max: &[Option<Scalar>]) -> Overlap {
+        (**self).classify(min, max)
+    }
+    fn contains(&self, point: &[Scalar]) -> bool {
+        (**self).contains(point)
+    }
+    fn dim(&self) -> Option<usize> {
+        (**self).dim()
+    }
+}
+
+/// The half-space `{ x | normal · x <= offset }`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HalfSpace<S, const D: usize> {
+    normal: [S; D],
+    offset: S,
+}
+
+impl<S: Float, const D: usize> HalfSpace<S, D> {
+    pub fn new(normal: [S; D], offset: S) -> Self {
+        Self { normal, offset }
+    }
+
+    /// The half-space behind the plane through `point`, opposite to `normal`.
+    pub fn from_point_normal(point: [S; D], normal: [S; D]) -> Self {
+        Self::new(normal, dot(&normal, &point))
+    }
+
+    fn signed_distance(&self, point: &[S]) -> S {
+        dot(&self.normal, point) - self.offset
+    }
+}
+
+impl<S: Float, const D: usize> Region<S> for HalfSpace<S, D> {
+    fn classify(&self, min: &[Option<S>], max: &[Option<S>]) -> Overlap {
+        let (lower, upper) = linear_range(&self.normal, min, max);
+        if upper <= self.offset {
+            Overlap::Inside
+        } else if lower > self.offset {
+            Overlap::Outside
+        } else {
+            Overlap::Partial
+        }
+    }
+    fn contains(&self, point: &[S]) -> bool {
+        self.signed_distance(point) <= S::zero()
+    }
+    fn dim(&self) -> Option<usize> {
+        Some(D)
+    }
+}
+
+/// The intersection of half-spaces, such as a view frustum of a camera.
+/// # Example
+/// ```
+/// use kd_tree::{ConvexPolytope, HalfSpace};
+/// // a frustum looking along +z, with a 90-degree field of view and the near and far planes.
+/// let frustum = ConvexPolytope::new(vec![
+///     HalfSpace::new([1.0, 0.0, -1.0], 0.0),
+///     HalfSpace::new([-1.0, 0.0, -1.0], 0.0),
+///     HalfSpace::new([0.0, 1.0, -1.0], 0.0),
+///     HalfSpace::new([0.0, -1.0, -1.0], 0.0),
+///     HalfSpace::new([0.0, 0.0, -1.0], -1.0),
+///     HalfSpace::new([0.0, 0.0, 1.0], 10.0),
+/// ]);
+/// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![
+///     [0.0, 0.0, 5.0], [4.0, 0.0, 5.0], [6.0, 0.0, 5.0], [0.0, 0.0, 0.5], [0.0, 0.0, -5.0],
+/// ]);
+/// let mut found = kdtree.within_region(&frustum);
+/// found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(found, vec![&[0.0, 0.0, 5.0], &[4.0, 0.0, 5.0]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolytope<S, const D: usize> {
+    planes: Vec<HalfSpace<S, D>>,
+}
+
+impl<S: Float, const D: usize> ConvexPolytope<S, D> {
+    pub fn new(planes: Vec<HalfSpace<S, D>>) -> Self {
+        Self { planes }
+    }
+
+    pub fn planes(&self) -> &[HalfSpace<S, D>] {
+        &self.planes
+    }
+}
+
+impl<S: Float, const D: usize> Region<S> for ConvexPolytope<S, D> {
+    fn classify(&self, min: &[Option<S>], max: &[Option<S>]) -> Overlap {
+        let mut overlap = Overlap::Inside;
+        for plane in &self.planes {
+            match plane.classify(min, max) {
+                Overlap::Outside => return Overlap::Outside,
+                Overlap::Partial => overlap = Overlap::Partial,
+                Overlap::Inside => {}
+            }
+        }
+        overlap
+    }
+    fn contains(&self, point: &[S]) -> bool {
+        self.planes.iter().all(|plane| plane.contains(point))
+    }
+    fn dim(&self) -> Option<usize> {
+        Some(D)
+    }
+}
+
+/// A solid ellipsoid, which may be rotated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipsoid<S, const D: usize> {
+    center: [S; D],
+    /// The semi-axes divided by their squared lengths.
+    inverse_axes: [[S; D]; D],
+    /// The half-extents of the bounding box.
+    extents: [S; D],
+}
+
+impl<S: Float, const D: usize> Ellipsoid<S, D> {
+    /// Creates an ellipsoid from the mutually orthogonal semi-axes.
+    /// # Example
+    /// ```
+    /// use kd_tree::Ellipsoid;
+    /// // an ellipse elongated along the diagonal.
+    /// let ellipse = Ellipsoid::new([0.0, 0.0], [[2.0, 2.0], [-0.5, 0.5]]);
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[2.0, 2.0], [2.0, -2.0], [0.5, 0.0]]);
+    /// assert_eq!(kdtree.within_region(&ellipse).len(), 2);
+    /// ```
+    pub fn new(center: [S; D], semi_axes: [[S; D]; D]) -> Self {
+        let mut inverse_axes = semi_axes;
+        for axis in &mut inverse_axes {
+            let squared_length = dot(axis, axis);
+            for x in axis.iter_mut() {
+                *x = *x / squared_length;
+            }
+        }
+        let mut extents = [S::zero(); D];
+        for (k, extent) in extents.iter_mut().enumerate() {
+            *extent = semi_axes
+                .iter()
+                .fold(S::zero(), |sum, axis| sum + axis[k] * axis[k])
+                .sqrt();
+        }
+        Self {
+            center,
+            inverse_axes,
+            extents,
+        }
+    }
+
+    /// Creates an ellipsoid whose axes are parallel to the coordinate axes.
+    pub fn axis_aligned(center: [S; D], radii: [S; D]) -> Self {
+        let mut semi_axes = [[S::zero(); D]; D];
+        for k in 0..D {
+            semi_axes[k][k] = radii[k];
+        }
+        Self::new(center, semi_axes)
+    }
+}
+
+impl<S: Float, const D: usize> Region<S> for Ellipsoid<S, D> {
+    fn classify(&self, min: &[Option<S>], max: &[Option<S>]) -> Overlap {
+        if !overlaps(min, max, |k| {
+            (
+                self.center[k] - self.extents[k],
+                self.center[k] + self.extents[k],
+            )
+        }) {
+            return Overlap::Outside;
+        }
+        // the box is inside if the sum of the largest squared projections onto the axes is at most 1,
+        // although the largest ones may be at different corners.
+        let mut sum = S::zero();
+        for axis in &self.inverse_axes {
+            let (lower, upper) = linear_range(axis, min, max);
+            let offset = dot(axis, &self.center);
+            let t = (lower - offset).abs().max((upper - offset).abs());
+            sum = sum + t * t;
+        }
+        if sum <= S::one() {
+            Overlap::Inside
+        } else {
+            Overlap::Partial
+        }
+    }
+    fn contains(&self, point: &[S]) -> bool {
+        let mut sum = S::zero();
+        for axis in &self.inverse_axes {
+            let t = (0..D).fold(S::zero(), |t, k| t + axis[k] * (point[k] - self.center[k]));
+            sum = sum + t * t;
+        }
+        sum <= S::one()
+    }
+    fn dim(&self) -> Option<usize> {
+        Some(D)
+    }
+}
+
+/// The points within `radius` from the segment between `a` and `b`.
+/// # Example
+/// ```
+/// let capsule = kd_tree::Capsule::new([0.0, 0.0], [10.0, 0.0], 1.0);
+/// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[5.0, 0.9], [10.5, 0.5], [11.0, 1.0], [-2.0, 0.0]]);
+/// assert_eq!(kdtree.within_region(&capsule).len(), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule<S, const D: usize> {
+    a: [S; D],
+    b: [S; D],
+    radius: S,
+}
+
+impl<S: Float, const D: usize> Capsule<S, D> {
+    pub fn new(a: [S; D], b: [S; D], radius: S) -> Self {
+        Self { a, b, radius }
+    }
+
+    /// Returns the squared distance from `point` to the segment.
+    fn squared_distance(&self, point: &[S]) -> S {
+        let mut ab = [S::zero(); D];
+        let mut ap = [S::zero(); D];
+        for k in 0..D {
+            ab[k] = self.b[k] - self.a[k];
+            ap[k] = point[k] - self.a[k];
+        }
+        let squared_length = dot(&ab, &ab);
+        let t = if squared_length > S::zero() {
+            (dot(&ab, &ap) / squared_length)
+                .max(S::zero())
+                .min(S::one())
+        } else {
+            S::zero()
+        };
+        (0..D).fold(S::zero(), |sum, k| {
+            let diff = ap[k] - t * ab[k];
+            sum + diff * diff
+        })
+    }
+}
+
+impl<S: Float, const D: usize> Region<S> for Capsule<S, D> {
+    fn classify(&self, min: &[Option<S>], max: &[Option<S>]) -> Overlap {
+        if !overlaps(min, max, |k| {
+            (
+                self.a[k].min(self.b[k]) - self.radius,
+                self.a[k].max(self.b[k]) + self.radius,
+            )
+        }) {
+            return Overlap::Outside;
+        }
+        if min.iter().chain(max).any(Option::is_none) {
+            return Overlap::Partial;
+        }
+        // every point of the box is within the half-diagonal from its center.
+        let two = S::one() + S::one();
+        let mut center = [S::zero(); D];
+        let mut squared_half_diagonal = S::zero();
+        for k in 0..D {
+            let (min, max) = (min[k].unwrap(), max[k].unwrap());
+            center[k] = (min + max) / two;
+            let half = (max - min) / two;
+            squared_half_diagonal = squared_half_diagonal + half * half;
+        }
+        if squared_half_diagonal.sqrt() + self.squared_distance(&center).sqrt() <= self.radius {
+            Overlap::Inside
+        } else {
+            Overlap::Partial
+        }
+    }
+    fn contains(&self, point: &[S]) -> bool {
+        self.squared_distance(point) <= self.radius * self.radius
+    }
+    fn dim(&self) -> Option<usize> {
+        Some(D)
+    }
+}
+
+/// A box which may be rotated.
+/// # Example
+/// ```
+/// // a square rotated by 45 degrees, whose half-diagonal is 1.
+/// let square = kd_tree::OrientedBox::new([0.0, 0.0], [[0.5, 0.5], [-0.5, 0.5]]);
+/// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[0.9, 0.0], [0.6, 0.6], [0.0, -0.5]]);
+/// assert_eq!(kdtree.within_region(&square).len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrientedBox<S, const D: usize> {
+    faces: ConvexPolytope<S, D>,
+    lower: [S; D],
+    upper: [S; D],
+}
+
+impl<S: Float, const D: usize> OrientedBox<S, D> {
+    /// Creates a box from its center and the mutually orthogonal half-extent vectors.
+    pub fn new(center: [S; D], half_axes: [[S; D]; D]) -> Self {
+        let mut planes = Vec::with_capacity(2 * D);
+        for axis in &half_axes {
+            let mut opposite = *axis;
+            opposite.iter_mut().for_each(|x| *x = -*x);
+            let (offset, extent) = (dot(axis, &center), dot(axis, axis));
+            planes.push(HalfSpace::new(*axis, offset + extent));
+            planes.push(HalfSpace::new(opposite, extent - offset));
+        }
+        let mut lower = center;
+        let mut upper = center;
+        for k in 0..D {
+            let extent = half_axes
+                .iter()
+                .fold(S::zero(), |sum, axis| sum + axis[k].abs());
+            lower[k] = lower[k] - extent;
+            upper[k] = upper[k] + extent;
+        }
+        Self {
+            faces: ConvexPolytope::new(planes),
+            lower,
+            upper,
+        }
+    }
+}
+
+impl<S: Float, const D: usize> Region<S> for OrientedBox<S, D> {
+    fn classify(&self, min: &[Option<S>], max: &[Option<S>]) -> Overlap {
+        if !overlaps(min, max, |k| (self.lower[k], self.upper[k])) {
+            return Overlap::Outside;
+        }
+        self.faces.classify(min, max)
+    }
+    fn contains(&self, point: &[S]) -> bool {
+        self.faces.contains(point)
+    }
+    fn dim(&self) -> Option<usize> {
+        Some(D)
+    }
+}
+
+fn dot<S: Float>(a: &[S], b: &[S]) -> S {
+    a.iter().zip(b).fold(S::zero(), |sum, (&a, &b)| sum + a * b)
+}
+
+/// Returns the range of `normal · x` over the box.
+fn linear_range<S: Float>(normal: &[S], min: &[Option<S>], max: &[Option<S>]) -> (S, S) {
+    let (mut lower, mut upper) = (S::zero(), S::zero());
+    for (k, &n) in normal.iter().enumerate() {
+        let min = min[k].unwrap_or_else(S::neg_infinity);
+        let max = max[k].unwrap_or_else(S::infinity);
+        if n > S::zero() {
+            lower = lower + n * min;
+            upper = upper + n * max;
+        } else if n < S::zero() {
+            lower = lower + n * max;
+            upper = upper + n * min;
+        }
+    }
+    (lower, upper)
+}
+
+fn overlaps<S: Float>(
+    min: &[Option<S>],
+    max: &[Option<S>],
+    bounds: impl Fn(usize) -> (S, S),
+) -> bool {
+    (0..min.len()).all(|k| {
+        let (lower, upper) = bounds(k);
+        let above_min = match min[k] {
+            Some(min) => min <= upper,
+            None => true,
+        };
+        let below_max = match max[k] {
+            Some(max) => lower <= max,
+            None => true,
+        };
+        above_min && below_max
+    })
+}
+
+impl_kd_queries! {
+    /// search items in an arbitrary region, such as a [`ConvexPolytope`] or an [`Ellipsoid`].
+    /// # Panics
+    /// Panics if [`Region::dim`] differs from the dimension of the kd-tree.
+    pub fn within_region_by<Scalar: Copy>(
+        &self,
+        region: &(impl Region<Scalar> + ?Sized),
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<&T> {
+        assert!(
+            region.dim().unwrap_or(N::to_usize()) == N::to_usize(),
+            "the dimension of the region differs from the kd-tree"
+        );
+        let mut results = Vec::new();
+        kd_for_each_within_region_by(
+            self.items(),
+            N::to_usize(),
+            self.split_axes(),
+            region,
+            coord,
+            |item| results.push(item),
+        );
+        results
+    }
+
+    /// search items in an arbitrary region, such as a [`ConvexPolytope`] or an [`Ellipsoid`].
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[1.0, 2.0], [3.0, 1.0], [2.0, 3.0]]);
+    /// let half_plane = kd_tree::HalfSpace::new([1.0, 1.0], 4.0);
+    /// assert_eq!(kdtree.within_region(&half_plane), vec![&[1.0, 2.0], &[3.0, 1.0]]);
+    /// ```
+    pub fn within_region(&self, region: &(impl Region<T::Scalar> + ?Sized)) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_region_by(region, |item, k| item.at(k))
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    pub fn within_region_by<Scalar: Copy>(
+        &self,
+        region: &(impl Region<Scalar> + ?Sized),
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) -> Vec<&usize> {
+        self.indices()
+            .within_region_by(region, |&index, k| coord(self.item(index), k))
+    }
+
+    pub fn within_region(&self, region: &(impl Region<T::Scalar> + ?Sized)) -> Vec<&usize>
+    where
+        T: KdPoint<Dim = N>,
+    {
+        self.within_region_by(region, |item, k| item.at(k))
+    }
+}
@@ -0,0 +1,624 @@
+use crate::nearests::{sort_by_distance, NearestHeap};
+use crate::sort::Axes;
+use crate::{ItemAndDistance, KdIndexTreeN, KdPoint};
+use num_traits::Float;
+use typenum::Unsigned;
+
+/// An item found by [`KdSliceN::first_along_ray`](crate::KdSliceN::first_along_ray).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RayHit<'a, T, Scalar> {
+    pub item: &'a T,
+    /// The parameter `t` of the point `origin + t * direction` on the ray which is nearest to the item.
+    pub t: Scalar,
+    /// The squared euclidean distance from the ray.
+    pub squared_distance: Scalar,
+}
+
+/// The segment `origin + t * direction` for `0 <= t <= t_max`, which is a ray if `t_max` is infinite.
+struct Line<S> {
+    origin: Vec<S>,
+    direction: Vec<S>,
+    squared_length: S,
+    t_max: S,
+}
+
+impl<S: Float> Line<S> {
+    fn segment(a: &impl KdPoint<Scalar = S>, b: &impl KdPoint<Scalar = S>, dim: usize) -> Self {
+        let origin: Vec<S> = (0..dim).map(|k| a.at(k)).collect();
+        let direction = (0..dim).map(|k| b.at(k) - origin[k]).collect();
+        Self::new(origin, direction, S::one())
+    }
+
+    fn ray(
+        origin: &impl KdPoint<Scalar = S>,
+        direction: &impl KdPoint<Scalar = S>,
+        dim: usize,
+    ) -> Self {
+        let origin = (0..dim).map(|k| origin.at(k)).collect();
+        let direction = (0..dim).map(|k| direction.at(k)).collect();
+        Self::new(origin, direction, S::infinity())
+    }
+
+    fn new(origin: Vec<S>, direction: Vec<S>, t_max: S) -> Self {
+        let squared_length = direction.iter().fold(S::zero(), |sum, &d| sum + d * d);
+        Self {
+            origin,
+            direction,
+            squared_length,
+            t_max,
+        }
+    }
+
+    /// Returns the parameter of the nearest point to `point` on the line.
+    fn parameter(&self, point: &[S]) -> S {
+        if self.squared_length == S::zero() {
+            return S::zero();
+        }
+        let dot = (0..point.len()).fold(S::zero(), |sum, k| {
+            sum + (point[k] - self.origin[k]) * self.direction[k]
+        });
+        (dot / self.squared_length).max(S::zero()).min(self.t_max)
+    }
+
+    fn squared_distance_at(&self, point: &[S], t: S) -> S {
+        (0..point.len()).fold(S::zero(), |sum, k| {
+            let diff = point[k] - (self.origin[k] + t * self.direction[k]);
+            sum + diff * diff
+        })
+    }
+
+    fn squared_distance(&self, point: &[S]) -> S {
+        self.squared_distance_at(point, self.parameter(point))
+    }
+
+    /// Returns the squared distances from the line to the lower side `x[k] <= split`
+    /// and to the upper side `x[k] >= split` of the hyperplane.
+    fn squared_distances_to_sides(&self, k: usize, split: S) -> (S, S) {
+        let (o, d) = (self.origin[k], self.direction[k]);
+        let (lower, upper) = if d == S::zero() {
+            (o, o)
+        } else if self.t_max.is_infinite() {
+            if d > S::zero() {
+                (o, S::infinity())
+            } else {
+                (S::neg_infinity(), o)
+            }
+        } else {
+            (o.min(o + d), o.max(o + d))
+        };
+        let below = (lower - split).max(S::zero());
+        let above = (split - upper).max(S::zero());
+        (below * below, above * above)
+    }
+}
+
+/// Collects the `num` nearest items to any of `lines`, in arbitrary order.
+/// The nodes are split along `axes`.
+fn kd_nearests_to_lines_by<'a, T, S: Float>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    lines: &[Line<S>],
+    num: usize,
+    coord: impl Fn(&T, usize) -> S + Copy,
+) -> Vec<ItemAndDistance<'a, T, S>> {
+    struct Context<'a, 'l, T, S, C> {
+        heap: NearestHeap<'a, T, S>,
+        point: Vec<S>,
+        dim: usize,
+        axes: Axes<'l>,
+        lines: &'l [Line<S>],
+        coord: C,
+    }
+    impl<'a, 'l, T, S: Float, C: Fn(&T, usize) -> S> Context<'a, 'l, T, S, C> {
+        fn recurse(&mut self, kdtree: &'a [T], offset: usize, axis: usize) {
+            let mid = kdtree.len() / 2;
+            let axis = self.axes.get(offset + mid, axis);
+            let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+            let coord = &self.coord;
+            self.point.clear();
+            self.point.extend((0..self.dim).map(|k| coord(item, k)));
+            let squared_distance = min_squared_distance(self.lines, &self.point);
+            if self.heap.admits(squared_distance, None) {
+                self.heap.push(item, squared_distance);
+            }
+            let (below, above) = squared_distances_to_sides(self.lines, axis, self.point[axis]);
+            let next_axis = (axis + 1) % self.dim;
+            let mut branches = [(lower, offset, below), (upper, offset + mid + 1, above)];
+            if above < below {
+                branches.swap(0, 1);
+            }
+            for &(branch, offset, bound) in &branches {
+                if !branch.is_empty() && self.heap.admits(bound, None) {
+                    self.recurse(branch, offset, next_axis);
+                }
+            }
+        }
+    }
+    if num == 0 || kdtree.is_empty() {
+        return Vec::new();
+    }
+    let mut context = Context {
+        heap: NearestHeap::new(num),
+        point: Vec::with_capacity(dim),
+        dim,
+        axes,
+        lines,
+        coord,
+    };
+    context.recurse(kdtree, 0, 0);
+    context.heap.into_vec()
+}
+
+/// Calls `f` for each item whose squared distance to any of `lines` is less than `squared_radius`.
+/// The nodes are split along `axes`.
+#[allow(clippy::too_many_arguments)]
+fn kd_for_each_within_lines_by<'a, T, S: Float>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    lines: &[Line<S>],
+    squared_radius: S,
+    coord: impl Fn(&T, usize) -> S + Copy,
+    mut f: impl FnMut(&'a T),
+) {
+    #[allow(clippy::too_many_arguments)]
+    fn recurse<'a, T, S: Float>(
+        kdtree: &'a [T],
+        offset: usize,
+        axis: usize,
+        dim: usize,
+        axes: Axes<'_>,
+        lines: &[Line<S>],
+        squared_radius: S,
+        coord: impl Fn(&T, usize) -> S + Copy,
+        point: &mut Vec<S>,
+        f: &mut impl FnMut(&'a T),
+    ) {
+        let mid = kdtree.len() / 2;
+        let axis = axes.get(offset + mid, axis);
+        let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+        point.clear();
+        point.extend((0..dim).map(|k| coord(item, k)));
+        if lines
+            .iter()
+            .any(|line| line.squared_distance(point) < squared_radius)
+        {
+            f(item);
+        }
+        let (below, above) = squared_distances_to_sides(lines, axis, point[axis]);
+        let next_axis = (axis + 1) % dim;
+        for &(branch, offset, bound) in &[(lower, offset, below), (upper, offset + mid + 1, above)]
+        {
+            if !branch.is_empty() && bound < squared_radius {
+                recurse(
+                    branch,
+                    offset,
+                    next_axis,
+                    dim,
+                    axes,
+                    lines,
+                    squared_radius,
+                    coord,
+                    point,
+                    f,
+                );
+            }
+        }
+    }
+    if kdtree.is_empty() {
+        return;
+    }
+    let mut point = Vec::with_capacity(dim);
+    recurse(
+        kdtree,
+        0,
+        0,
+        dim,
+        axes,
+        lines,
+        squared_radius,
+        coord,
+        &mut point,
+        &mut f,
+    );
+}
+
+/// Returns the item within `tolerance` from `ray` with the least parameter along the ray.
+/// The nodes are split along `axes`.
+fn kd_first_along_ray_by<'a, T, S: Float>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    ray: &Line<S>,
+    tolerance: S,
+    coord: impl Fn(&T, usize) -> S + Copy,
+) -> Option<RayHit<'a, T, S>> {
+    struct Context<'a, 'r, T, S, C> {
+        first: Option<RayHit<'a, T, S>>,
+        point: Vec<S>,
+        dim: usize,
+        axes: Axes<'r>,
+        ray: &'r Line<S>,
+        tolerance: S,
+        coord: C,
+    }
+    impl<'a, 'r, T, S: Float, C: Fn(&T, usize) -> S> Context<'a, 'r, T, S, C> {
+        /// Returns the range of the parameter where the ray is within `tolerance` from the side of the hyperplane.
+        fn parameter_range(&self, axis: usize, split: S, upper: bool) -> (S, S) {
+            let (o, d) = (self.ray.origin[axis], self.ray.direction[axis]);
+            // the ray must satisfy `o + t * d >= bound` for the upper side, or `o + t * d <= bound` for the lower side.
+            let bound = if upper {
+                split - self.tolerance
+            } else {
+                split + self.tolerance
+            };
+            if d == S::zero() {
+                let inside = if upper { o >= bound } else { o <= bound };
+                return if inside {
+                    (S::zero(), S::infinity())
+                } else {
+                    (S::infinity(), S::neg_infinity())
+                };
+            }
+            let t = (bound - o) / d;
+            if (d > S::zero()) == upper {
+                (t.max(S::zero()), S::infinity())
+            } else {
+                (S::zero(), t)
+            }
+        }
+
+        /// Returns whether the parameter `t` is before the first hit found so far.
+        fn is_before_first(&self, t: S) -> bool {
+            match &self.first {
+                Some(first) => t < first.t,
+                None => true,
+            }
+        }
+
+        fn recurse(&mut self, kdtree: &'a [T], offset: usize, axis: usize) {
+            let mid = kdtree.len() / 2;
+            let axis = self.axes.get(offset + mid, axis);
+            let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+            let coord = &self.coord;
+            self.point.clear();
+            self.point.extend((0..self.dim).map(|k| coord(item, k)));
+            let t = self.ray.parameter(&self.point);
+            if self.is_before_first(t) {
+                let squared_distance = self.ray.squared_distance_at(&self.point, t);
+                if squared_distance <= self.tolerance * self.tolerance {
+                    self.first = Some(RayHit {
+                        item,
+                        t,
+                        squared_distance,
+                    });
+                }
+            }
+            let split = self.point[axis];
+            let next_axis = (axis + 1) % self.dim;
+            let mut branches = [
+                (lower, offset, self.parameter_range(axis, split, false)),
+                (
+                    upper,
+                    offset + mid + 1,
+                    self.parameter_range(axis, split, true),
+                ),
+            ];
+            if branches[1].2 .0 < branches[0].2 .0 {
+                branches.swap(0, 1);
+            }
+            for &(branch, offset, (t_min, t_max)) in &branches {
+                if !branch.is_empty() && t_min <= t_max && self.is_before_first(t_min) {
+                    self.recurse(branch, offset, next_axis);
+                }
+            }
+        }
+    }
+    if kdtree.is_empty() {
+        return None;
+    }
+    let mut context = Context {
+        first: None,
+        point: Vec::with_capacity(dim),
+        dim,
+        axes,
+        ray,
+        tolerance,
+        coord,
+    };
+    context.recurse(kdtree, 0, 0);
+    context.first
+}
+
+fn min_squared_distance<S: Float>(lines: &[Line<S>], point: &[S]) -> S {
+    lines.iter().fold(S::infinity(), |min, line| {
+        min.min(line.squared_distance(point))
+    })
+}
+
+fn squared_distances_to_sides<S: Float>(lines: &[Line<S>], axis: usize, split: S) -> (S, S) {
+    lines
+        .iter()
+        .fold((S::infinity(), S::infinity()), |(below, above), line| {
+            let (b, a) = line.squared_distances_to_sides(axis, split);
+            (below.min(b), above.min(a))
+        })
+}
+
+impl_kd_queries! {
+    /// Returns the nearest item to the segment from `a` to `b`, with the squared distance to the segment.
+    /// Returns `None` if `self.is_empty()`.
+    pub fn nearest_to_segment_by<Q: KdPoint<Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, T, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        self.nearests_to_segment_by(a, b, 1, coord).pop()
+    }
+
+    /// Returns the nearest item to the segment from `a` to `b`, with the squared distance to the segment.
+    /// Returns `None` if `self.is_empty()`.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[0.0, 2.0], [5.0, 1.0], [12.0, 0.0]]);
+    /// let found = kdtree.nearest_to_segment(&[0.0, 0.0], &[10.0, 0.0]).unwrap();
+    /// assert_eq!(found.item, &[5.0, 1.0]);
+    /// assert_eq!(found.squared_distance, 1.0);
+    /// ```
+    pub fn nearest_to_segment<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+    ) -> Option<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.nearest_to_segment_by(a, b, |item, k| item.at(k))
+    }
+
+    /// Returns `num` nearest items to the segment from `a` to `b`, sorted by the squared distance to the segment.
+    pub fn nearests_to_segment_by<Q: KdPoint<Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, T, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        let dim = Q::dim();
+        let lines = [Line::segment(a, b, dim)];
+        let mut nearests =
+            kd_nearests_to_lines_by(self.items(), dim, self.split_axes(), &lines, num, coord);
+        sort_by_distance(&mut nearests);
+        nearests
+    }
+
+    /// Returns `num` nearest items to the segment from `a` to `b`, sorted by the squared distance to the segment.
+    pub fn nearests_to_segment<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.nearests_to_segment_by(a, b, num, |item, k| item.at(k))
+    }
+
+    /// search items whose distances to the polyline through `vertices` are less than `distance`.
+    /// A single vertex is regarded as a point.
+    pub fn within_distance_of_polyline_by<Q: KdPoint<Dim = N>>(
+        &self,
+        vertices: &[Q],
+        distance: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&T>
+    where
+        Q::Scalar: Float,
+    {
+        let dim = Q::dim();
+        let lines: Vec<_> = if vertices.len() == 1 {
+            vec![Line::segment(&vertices[0], &vertices[0], dim)]
+        } else {
+            vertices
+                .windows(2)
+                .map(|pair| Line::segment(&pair[0], &pair[1], dim))
+                .collect()
+        };
+        let mut results = Vec::new();
+        if !lines.is_empty() {
+            kd_for_each_within_lines_by(
+                self.items(),
+                dim,
+                self.split_axes(),
+                &lines,
+                distance * distance,
+                coord,
+                |item| results.push(item),
+            );
+        }
+        results
+    }
+
+    /// search items whose distances to the polyline through `vertices` are less than `distance`.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![[5.0, 0.5], [10.5, 5.0], [5.0, 5.0]]);
+    /// let path = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]];
+    /// assert_eq!(kdtree.within_distance_of_polyline(&path, 1.0).len(), 2);
+    /// ```
+    pub fn within_distance_of_polyline<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        vertices: &[Q],
+        distance: T::Scalar,
+    ) -> Vec<&T>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.within_distance_of_polyline_by(vertices, distance, |item, k| item.at(k))
+    }
+
+    /// Returns the first item along the ray from `origin` toward `direction`,
+    /// among the items whose distances to the ray are not greater than `tolerance`.
+    /// The items are ordered by the parameter of their projections onto the ray.
+    pub fn first_along_ray_by<Q: KdPoint<Dim = N>>(
+        &self,
+        origin: &Q,
+        direction: &Q,
+        tolerance: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<RayHit<'_, T, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        let dim = Q::dim();
+        let ray = Line::ray(origin, direction, dim);
+        kd_first_along_ray_by(self.items(), dim, self.split_axes(), &ray, tolerance, coord)
+    }
+
+    /// Returns the first item along the ray from `origin` toward `direction`,
+    /// among the items whose distances to the ray are not greater than `tolerance`.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build_by_ordered_float(vec![
+    ///     [1.0, 0.0, 9.0], [1.0, 0.05, 5.0], [0.0, 0.0, -1.0], [3.0, 0.0, 1.0],
+    /// ]);
+    /// let hit = kdtree.first_along_ray(&[1.0, 0.0, 0.0], &[0.0, 0.0, 1.0], 0.1).unwrap();
+    /// assert_eq!(hit.item, &[1.0, 0.05, 5.0]);
+    /// assert_eq!(hit.t, 5.0);
+    /// ```
+    pub fn first_along_ray<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        origin: &Q,
+        direction: &Q,
+        tolerance: T::Scalar,
+    ) -> Option<RayHit<'_, T, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.first_along_ray_by(origin, direction, tolerance, |item, k| item.at(k))
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    pub fn nearest_to_segment_by<Q: KdPoint<Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<ItemAndDistance<'_, usize, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        self.indices()
+            .nearest_to_segment_by(a, b, |&index, k| coord(self.item(index), k))
+    }
+
+    pub fn nearest_to_segment<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+    ) -> Option<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.nearest_to_segment_by(a, b, |item, k| item.at(k))
+    }
+
+    pub fn nearests_to_segment_by<Q: KdPoint<Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        num: usize,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<ItemAndDistance<'_, usize, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        self.indices()
+            .nearests_to_segment_by(a, b, num, |&index, k| coord(self.item(index), k))
+    }
+
+    pub fn nearests_to_segment<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        a: &Q,
+        b: &Q,
+        num: usize,
+    ) -> Vec<ItemAndDistance<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.nearests_to_segment_by(a, b, num, |item, k| item.at(k))
+    }
+
+    pub fn within_distance_of_polyline_by<Q: KdPoint<Dim = N>>(
+        &self,
+        vertices: &[Q],
+        distance: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Vec<&usize>
+    where
+        Q::Scalar: Float,
+    {
+        self.indices()
+            .within_distance_of_polyline_by(vertices, distance, |&index, k| {
+                coord(self.item(index), k)
+            })
+    }
+
+    pub fn within_distance_of_polyline<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        vertices: &[Q],
+        distance: T::Scalar,
+    ) -> Vec<&usize>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.within_distance_of_polyline_by(vertices, distance, |item, k| item.at(k))
+    }
+
+    pub fn first_along_ray_by<Q: KdPoint<Dim = N>>(
+        &self,
+        origin: &Q,
+        direction: &Q,
+        tolerance: Q::Scalar,
+        coord: impl Fn(&T, usize) -> Q::Scalar + Copy,
+    ) -> Option<RayHit<'_, usize, Q::Scalar>>
+    where
+        Q::Scalar: Float,
+    {
+        self.indices()
+            .first_along_ray_by(origin, direction, tolerance, |&index, k| {
+                coord(self.item(index), k)
+            })
+    }
+
+    pub fn first_along_ray<Q: KdPoint<Scalar = T::Scalar, Dim = N>>(
+        &self,
+        origin: &Q,
+        direction: &Q,
+        tolerance: T::Scalar,
+    ) -> Option<RayHit<'_, usize, T::Scalar>>
+    where
+        T: KdPoint<Dim = N>,
+        T::Scalar: Float,
+    {
+        self.first_along_ray_by(origin, direction, tolerance, |item, k| item.at(k))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::sort::{compare_by_kd_point, is_kd_sorted_with_axes_by, Axes};
+use crate::{KdIndexTreeN, KdPoint, KdSliceN, KdTreeN, KnnGraph};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+use typenum::Unsigned;
+
+impl<T: Serialize, N: Unsigned> Serialize for KdSliceN<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items().serialize(serializer)
+    }
+}
+
+/// Serializes the items and the split axes of the nodes.
+/// The axes are empty unless the tree is built with a [`SplitStrategy`](crate::SplitStrategy).
+impl<T: Serialize, N: Unsigned> Serialize for KdTreeN<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("KdTree", 2)?;
+        state.serialize_field("items", &self.1)?;
+        state.serialize_field("axes", &self.2)?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "KdTree")]
+struct Parts<T> {
+    items: Vec<T>,
+    #[serde(default)]
+    axes: Vec<u16>,
+}
+
+/// Deserializes a tree serialized from a [`KdTreeN`],
+/// and fails unless the items are in the order produced by [`KdTreeN::build_by`] or the like.
+impl<'de, T, N: Unsigned> Deserialize<'de> for KdTreeN<T, N>
+where
+    T: KdPoint<Dim = N> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Parts { items, axes } = Parts::deserialize(deserializer)?;
+        if is_kd_sorted_with_axes_by(&items, N::to_usize(), Axes::new(&axes), compare_by_kd_point) {
+            Ok(Self(PhantomData, items, axes))
+        } else {
+            Err(D::Error::custom("items are not sorted as a kd-tree"))
+        }
+    }
+}
+
+impl<T, N: Unsigned> KdTreeN<T, N> {
+    /// Deserializes a tree without checking that the items are sorted as a kd-tree.
+    /// It is intended for data serialized from a [`KdTreeN`].
+    ///
+    /// # Safety
+    /// The items must be in the order produced by [`KdTreeN::build_by`] or the like,
+    /// and the split axes must be those of the tree which has been serialized.
+    /// Otherwise the queries on the returned tree return unspecified results or panic.
+    /// # Example
+    /// ```
+    /// let kdtree = kd_tree::KdTree::build(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]]);
+    /// let json = serde_json::to_string(&kdtree).unwrap();
+    /// let mut deserializer = serde_json::Deserializer::from_str(&json);
+    /// let deserialized = unsafe { kd_tree::KdTree::deserialize_unchecked(&mut deserializer) }.unwrap();
+    /// assert_eq!(kdtree, deserialized);
+    /// ```
+    pub unsafe fn deserialize_unchecked<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let Parts { items, axes } = Parts::deserialize(deserializer)?;
+        Ok(Self(PhantomData, items, axes))
+    }
+}
+
+/// Serializes the kd-tree of indices, but not the source items.
+impl<'a, T, N: Unsigned> Serialize for KdIndexTreeN<'a, T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.indices().serialize(serializer)
+    }
+}
+
+impl<'a, T, N: Unsigned> KdIndexTreeN<'a, T, N> {
+    /// Deserializes a kd-tree of indices to the `source`, which has been serialized from a [`KdIndexTreeN`].
+    /// Fails unless the indices are a permutation of `0..source.len()` sorted as a kd-tree.
+    /// # Example
+    /// ```
+    /// let items = vec![[1, 2, 3], [3, 1, 2], [2, 3, 1]];
+    /// let kdtree = kd_tree::KdIndexTree::build(&items);
+    /// let json = serde_json::to_string(&kdtree).unwrap();
+    /// let mut deserializer = serde_json::Deserializer::from_str(&json);
+    /// let deserialized = kd_tree::KdIndexTree::deserialize_indices(&items, &mut deserializer).unwrap();
+    /// assert_eq!(kdtree, deserialized);
+    /// ```
+    pub fn deserialize_indices<'de, D>(source: &'a [T], deserializer: D) -> Result<Self, D::Error>
+    where
+        T: KdPoint<Dim = N>,
+        D: Deserializer<'de>,
+    {
+        let Parts {
+            items: indices,
+            axes,
+        } = Parts::<usize>::deserialize(deserializer)?;
+        let mut found = vec![false; source.len()];
+        for &i in &indices {
+            if i >= source.len() || std::mem::replace(&mut found[i], true) {
+                return Err(D::Error::custom(
+                    "indices are not a permutation of the source",
+                ));
+            }
+        }
+        if indices.len() != source.len() {
+            return Err(D::Error::custom(
+                "indices are not a permutation of the source",
+            ));
+        }
+        if !is_kd_sorted_with_axes_by(&indices, N::to_usize(), Axes::new(&axes), |&i1, &i2, k| {
+            compare_by_kd_point(&source[i1], &source[i2], k)
+        }) {
+            return Err(D::Error::custom("indices are not sorted as a kd-tree"));
+        }
+        Ok(Self {
+            source,
+            kdtree: KdTreeN(PhantomData, indices, axes),
+        })
+    }
+
+    /// Same as [`KdIndexTreeN::deserialize_indices`], but doesn't check the indices.
+    ///
+    /// # Safety
+    /// The indices must be a permutation of `0..source.len()` in the order produced by [`KdIndexTreeN::build_by`] or the like,
+    /// and the split axes must be those of the tree which has been serialized.
+    /// Otherwise the queries on the returned tree return unspecified results or panic.
+    pub unsafe fn deserialize_indices_unchecked<'de, D>(
+        source: &'a [T],
+        deserializer: D,
+    ) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self {
+            source,
+            kdtree: KdTreeN::deserialize_unchecked(deserializer)?,
+        })
+    }
+}
+
+/// Deserializes a graph serialized from a [`KnnGraph`], and fails unless the offsets are consistent with the neighbors.
+impl<'de, Scalar: Deserialize<'de>> Deserialize<'de> for KnnGraph<Scalar> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "KnnGraph")]
+        struct Parts<Scalar> {
+            offsets: Vec<usize>,
+            neighbors: Vec<usize>,
+            squared_distances: Vec<Scalar>,
+        }
+        let parts = Parts::deserialize(deserializer)?;
+        KnnGraph::from_parts(parts.offsets, parts.neighbors, parts.squared_distances)
+            .map_err(D::Error::custom)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::KdPoint;
+use crate::{KdPoint, SplitStrategy};
 use std::cmp::Ordering;
 
 #[allow(dead_code)]
@@ -19,6 +19,14 @@ where
     })
 }
 
+/// Compares coordinates of points, regarding incomparable values such as NaN as equal.
+pub fn compare_by_kd_point<T: KdPoint>(item1: &T, item2: &T, k: usize) -> Ordering {
+    item1
+        .at(k)
+        .partial_cmp(&item2.at(k))
+        .unwrap_or(Ordering::Equal)
+}
+
 #[allow(dead_code)]
 pub fn kd_sort_by_key<T, Key: Ord>(
     items: &mut [T],
@@ -35,18 +43,228 @@ pub fn kd_sort_by<T>(
     dim: usize,
     kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
 ) {
-    fn recurse<T>(
+    kd_sort_by_from(items, 0, dim, kd_compare)
+}
+
+/// Sorts a subtree whose root splits along `axis`.
+fn kd_sort_by_from<T>(
+    items: &mut [T],
+    axis: usize,
+    dim: usize,
+    kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+) {
+    if items.len() >= 2 {
+        pdqselect::select_by(items, items.len() / 2, |x, y| kd_compare(x, y, axis));
+        let mid = items.len() / 2;
+        let axis = (axis + 1) % dim;
+        kd_sort_by_from(&mut items[..mid], axis, dim, kd_compare);
+        kd_sort_by_from(&mut items[mid + 1..], axis, dim, kd_compare);
+    }
+}
+
+/// The split axes of the nodes of a kd-tree in the implicit layout, where the middle item of every subtree is its root.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Axes<'a> {
+    /// The root is split along the axis 0, and the children of a node along the next axis of it.
+    RoundRobin,
+    /// `axes[i]` is the split axis of the node whose item is at `i`.
+    PerNode(&'a [u16]),
+}
+
+impl<'a> Axes<'a> {
+    /// Returns [`Axes::RoundRobin`] if `axes` is empty, or [`Axes::PerNode`] otherwise.
+    pub fn new(axes: &'a [u16]) -> Self {
+        if axes.is_empty() {
+            Axes::RoundRobin
+        } else {
+            Axes::PerNode(axes)
+        }
+    }
+
+    /// Returns the split axis of the node at `position`, whose axis in the round robin order is `axis`.
+    pub fn get(self, position: usize, axis: usize) -> usize {
+        match self {
+            Axes::RoundRobin => axis,
+            Axes::PerNode(axes) => axes[position] as usize,
+        }
+    }
+}
+
+/// Sorts the items into the implicit layout, splitting each subtree at the median along the axis chosen by `strategy`,
+/// and returns the split axis of each node as in [`Axes::PerNode`], or an empty `Vec` for [`SplitStrategy::RoundRobin`].
+/// `compare` partitions the items, and `coord` measures the spread of the coordinates.
+/// # Panics
+/// Panics for [`SplitStrategy::SlidingMidpoint`], because the implicit layout splits every node at the median.
+pub fn kd_sort_with_strategy_by<T, Scalar>(
+    items: &mut [T],
+    dim: usize,
+    strategy: SplitStrategy,
+    compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+    coord: impl Fn(&T, usize) -> Scalar + Copy,
+) -> Vec<u16>
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    fn recurse<T, Scalar>(
         items: &mut [T],
+        axes: &mut [u16],
+        dim: usize,
+        compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+        coord: impl Fn(&T, usize) -> Scalar + Copy,
+    ) where
+        Scalar: num_traits::NumAssign + Copy + PartialOrd,
+    {
+        if items.is_empty() {
+            return;
+        }
+        let axis = max_spread_axis(items, dim, coord);
+        let mid = items.len() / 2;
+        pdqselect::select_by(items, mid, |x, y| compare(x, y, axis));
+        axes[mid] = axis as u16;
+        let (lower, upper) = items.split_at_mut(mid);
+        let (lower_axes, upper_axes) = axes.split_at_mut(mid);
+        recurse(lower, lower_axes, dim, compare, coord);
+        recurse(&mut upper[1..], &mut upper_axes[1..], dim, compare, coord);
+    }
+    match strategy {
+        SplitStrategy::RoundRobin => {
+            kd_sort_by(items, dim, compare);
+            Vec::new()
+        }
+        SplitStrategy::MaxSpread => {
+            assert!(dim > 0, "the dimension of a kd-tree must be positive");
+            assert!(
+                dim <= u16::MAX as usize + 1,
+                "the dimension is too large to store the split axes"
+            );
+            let mut axes = vec![0; items.len()];
+            recurse(items, &mut axes, dim, compare, coord);
+            axes
+        }
+        SplitStrategy::SlidingMidpoint => panic!(
+            "the sliding midpoint split is only supported by the bucket kd-tree, \
+             because the implicit layout splits every node at the median"
+        ),
+    }
+}
+
+/// Returns the axis along which the coordinates of the items spread the most.
+fn max_spread_axis<T, Scalar>(items: &[T], dim: usize, coord: impl Fn(&T, usize) -> Scalar) -> usize
+where
+    Scalar: num_traits::NumAssign + Copy + PartialOrd,
+{
+    let mut max = (0, Scalar::zero());
+    for k in 0..dim {
+        let (mut min_k, mut max_k) = (coord(&items[0], k), coord(&items[0], k));
+        for item in &items[1..] {
+            let x = coord(item, k);
+            if x < min_k {
+                min_k = x;
+            }
+            if x > max_k {
+                max_k = x;
+            }
+        }
+        if max_k - min_k > max.1 {
+            max = (k, max_k - min_k);
+        }
+    }
+    max.0
+}
+
+/// Checks that the items are in the order produced by [`KdSliceN::sort_by`](crate::KdSliceN::sort_by),
+/// i.e. the middle item of every subtree splits the others along the axis of the subtree.
+/// An empty slice is always sorted.
+/// # Panics
+/// Panics if `items` is not empty and `dim` is zero.
+/// # Example
+/// ```
+/// let compare = |item1: &[i32; 2], item2: &[i32; 2], k: usize| item1[k].cmp(&item2[k]);
+/// let mut items = vec![[1, 2], [2, 1], [3, 3], [0, 0]];
+/// assert!(!kd_tree::is_kd_sorted_by(&items, 2, compare));
+/// kd_tree::KdSlice2::sort_by(&mut items, compare);
+/// assert!(kd_tree::is_kd_sorted_by(&items, 2, compare));
+/// assert!(kd_tree::is_kd_sorted_by(&[] as &[[i32; 2]], 2, compare));
+/// ```
+pub fn is_kd_sorted_by<T>(
+    items: &[T],
+    dim: usize,
+    kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+) -> bool {
+    is_kd_sorted_with_axes_by(items, dim, Axes::RoundRobin, kd_compare)
+}
+
+/// Same as [`is_kd_sorted_by`], but the nodes are split along `axes`.
+/// Also checks that `axes` has an axis less than `dim` for every item.
+pub(crate) fn is_kd_sorted_with_axes_by<T>(
+    items: &[T],
+    dim: usize,
+    axes: Axes<'_>,
+    kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+) -> bool {
+    fn recurse<T>(
+        items: &[T],
+        offset: usize,
         axis: usize,
         dim: usize,
+        axes: Axes<'_>,
         kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy,
+    ) -> bool {
+        if items.len() < 2 {
+            return true;
+        }
+        let mid = items.len() / 2;
+        let axis = axes.get(offset + mid, axis);
+        let (lower, item, upper) = (&items[..mid], &items[mid], &items[mid + 1..]);
+        let next_axis = (axis + 1) % dim;
+        lower
+            .iter()
+            .all(|x| kd_compare(x, item, axis) != Ordering::Greater)
+            && upper
+                .iter()
+                .all(|x| kd_compare(x, item, axis) != Ordering::Less)
+            && recurse(lower, offset, next_axis, dim, axes, kd_compare)
+            && recurse(upper, offset + mid + 1, next_axis, dim, axes, kd_compare)
+    }
+    if let Axes::PerNode(axes) = axes {
+        if axes.len() != items.len() || axes.iter().any(|&axis| axis as usize >= dim) {
+            return false;
+        }
+    }
+    if items.is_empty() {
+        return true;
+    }
+    assert!(dim > 0, "the dimension of a kd-tree must be positive");
+    recurse(items, 0, 0, dim, axes, kd_compare)
+}
+
+/// Subtrees smaller than this are sorted sequentially by [`kd_par_sort_by`].
+#[cfg(feature = "rayon")]
+const PAR_SORT_THRESHOLD: usize = 1 << 12;
+
+#[cfg(feature = "rayon")]
+pub fn kd_par_sort_by<T: Send>(
+    items: &mut [T],
+    dim: usize,
+    kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy + Send + Sync,
+) {
+    fn recurse<T: Send>(
+        items: &mut [T],
+        axis: usize,
+        dim: usize,
+        kd_compare: impl Fn(&T, &T, usize) -> Ordering + Copy + Send + Sync,
     ) {
-        if items.len() >= 2 {
+        if items.len() < PAR_SORT_THRESHOLD {
+            kd_sort_by_from(items, axis, dim, kd_compare);
+        } else {
             pdqselect::select_by(items, items.len() / 2, |x, y| kd_compare(x, y, axis));
             let mid = items.len() / 2;
             let axis = (axis + 1) % dim;
-            recurse(&mut items[..mid], axis, dim, kd_compare);
-            recurse(&mut items[mid + 1..], axis, dim, kd_compare);
+            let (lower, upper) = items.split_at_mut(mid);
+            rayon::join(
+                || recurse(lower, axis, dim, kd_compare),
+                || recurse(&mut upper[1..], axis, dim, kd_compare),
+            );
         }
     }
     recurse(items, 0, dim, kd_compare);
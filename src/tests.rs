@@ -73,6 +73,1149 @@ fn test_within_radius() {
     }
 }
 
+#[test]
+fn test_metrics() {
+    check_metric(&SquaredEuclidean);
+    check_metric(&Manhattan);
+    check_metric(&Chebyshev);
+    check_metric(&Minkowski::new(3.0));
+    check_metric(&WeightedSquaredEuclidean::new(vec![1.0, 4.0, 0.5]));
+}
+
+#[test]
+#[should_panic(expected = "the number of weights does not match the dimension")]
+fn test_weights_mismatch() {
+    let kdtree = KdTree::build_by_ordered_float(vec![[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]]);
+    kdtree.nearest_with_metric(
+        &[0.5, 0.5, 0.5],
+        &WeightedSquaredEuclidean::new(vec![1.0, 2.0, 3.0, 4.0]),
+    );
+}
+
+fn check_metric(metric: &impl Metric<f64>) {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    let distance = |p: &[f64; 3], q: &[f64; 3]| metric.distance(3, |k| q[k] - p[k]);
+    const NUM: usize = 5;
+    for _ in 0..100 {
+        let query = gen3d();
+        let found = kdtree.nearest_with_metric(&query, metric).unwrap();
+        let expected = kdtree
+            .iter()
+            .min_by_key(|p| ordered_float::OrderedFloat(distance(p, &query)))
+            .unwrap();
+        assert_eq!(found.item, expected);
+
+        let found = kdtree.nearests_with_metric(&query, NUM, metric);
+        assert_eq!(found.len(), NUM);
+        for i in 1..found.len() {
+            assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+        }
+        let count = kdtree
+            .iter()
+            .filter(|p| distance(p, &query) <= found[NUM - 1].squared_distance)
+            .count();
+        assert_eq!(count, NUM);
+
+        let radius = found[NUM - 1].squared_distance;
+        let found = kdtree.within_distance_with_metric(&query, radius, metric);
+        let count = kdtree
+            .iter()
+            .filter(|p| distance(p, &query) < radius)
+            .count();
+        assert_eq!(found.len(), count);
+    }
+}
+
+#[test]
+fn test_nearests_approx() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    const NUM: usize = 5;
+    const EPS: f64 = 0.5;
+    for _ in 0..100 {
+        let query = gen3d();
+        let exact = kdtree.nearests(&query, NUM);
+        let found = kdtree.nearests_approx(&query, NUM, EPS);
+        assert_eq!(found.len(), NUM);
+        for i in 0..NUM {
+            assert!(
+                found[i].squared_distance <= exact[i].squared_distance * (1.0 + EPS) * (1.0 + EPS)
+            );
+        }
+        let found = kdtree.nearest_approx(&query, EPS).unwrap();
+        assert!(found.squared_distance <= exact[0].squared_distance * (1.0 + EPS) * (1.0 + EPS));
+    }
+}
+
+#[test]
+fn test_nearests_with_budget() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    for _ in 0..100 {
+        let query = gen3d();
+        let found = kdtree.nearests_with_budget(&query, 5, 3);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            kdtree.nearest_with_budget(&query, kdtree.len()),
+            kdtree.nearest(&query)
+        );
+    }
+}
+
+#[test]
+fn test_dynamic() {
+    let mut gen3d = random3d_generator();
+    let mut kdtree = KdDynamicTree::new_by_ordered_float();
+    let mut points = vec(1000, |_| gen3d());
+    kdtree.extend(points.iter().cloned());
+    assert_eq!(kdtree.len(), points.len());
+    for (i, bucket) in kdtree.buckets().iter().enumerate() {
+        assert!(bucket.len() <= 1 << i);
+    }
+    for i in (0..points.len()).step_by(3) {
+        assert!(kdtree.remove(&points[i]));
+    }
+    let mut i = 0;
+    points.retain(|_| {
+        i += 1;
+        (i - 1) % 3 != 0
+    });
+    assert_eq!(kdtree.len(), points.len());
+    for (i, bucket) in kdtree.buckets().iter().enumerate() {
+        assert!(bucket.is_empty() || 2 * bucket.len() >= 1 << i);
+    }
+    let expected = KdTree::build_by_ordered_float(points);
+    for _ in 0..100 {
+        let query = gen3d();
+        assert_eq!(
+            kdtree.nearest(&query).unwrap().item,
+            expected.nearest(&query).unwrap().item
+        );
+        let found = kdtree.nearests(&query, 5);
+        assert_eq!(found.len(), 5);
+        for (found, expected) in found.iter().zip(expected.nearests(&query, 5)) {
+            assert_eq!(found.squared_distance, expected.squared_distance);
+        }
+        assert_eq!(
+            kdtree.within_radius(&query, 0.2).len(),
+            expected.within_radius(&query, 0.2).len()
+        );
+    }
+    assert_eq!(
+        kdtree.remove_by(|p| p[0] < 0.5),
+        expected.iter().filter(|p| p[0] < 0.5).count()
+    );
+    assert!(kdtree.iter().all(|p| p[0] >= 0.5));
+    for (i, bucket) in kdtree.buckets().iter().enumerate() {
+        assert!(bucket.is_empty() || 2 * bucket.len() >= 1 << i);
+    }
+    kdtree.remove_by(|_| true);
+    assert!(kdtree.buckets().is_empty());
+}
+
+#[test]
+fn test_nearest_iter() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(1000, |_| gen3d()));
+    for _ in 0..10 {
+        let query = gen3d();
+        let found: Vec<_> = kdtree.nearest_iter(&query).collect();
+        assert_eq!(found.len(), kdtree.len());
+        for i in 1..found.len() {
+            assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+        }
+        let expected = kdtree.nearests(&query, 10);
+        for (found, expected) in found.iter().zip(expected) {
+            assert_eq!(found.squared_distance, expected.squared_distance);
+        }
+    }
+}
+
+#[test]
+fn test_nearests_within_radius() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    const NUM: usize = 10;
+    const RADIUS: f64 = 0.05;
+    for _ in 0..100 {
+        let query = gen3d();
+        let found = kdtree.nearests_within_radius(&query, NUM, RADIUS);
+        let expected: Vec<_> = kdtree
+            .nearests(&query, NUM)
+            .into_iter()
+            .filter(|p| p.squared_distance < RADIUS * RADIUS)
+            .collect();
+        assert_eq!(found.len(), expected.len());
+        for (found, expected) in found.iter().zip(expected) {
+            assert_eq!(found.squared_distance, expected.squared_distance);
+        }
+    }
+}
+
+#[test]
+fn test_within_radius_with_distance() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    const RADIUS: f64 = 0.1;
+    for _ in 0..100 {
+        let query = gen3d();
+        let options = WithinRadiusOptions {
+            sorted: true,
+            inclusive: false,
+        };
+        let found = kdtree.within_radius_with_distance(&query, RADIUS, options);
+        assert_eq!(found.len(), kdtree.within_radius(&query, RADIUS).len());
+        for i in 1..found.len() {
+            assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+        }
+        for found in found {
+            assert_eq!(found.squared_distance, squared_distance(found.item, &query));
+        }
+    }
+    let kdtree = KdTree::build(vec![[0, 0], [3, 4], [-3, 4], [1, 1]]);
+    let options = WithinRadiusOptions::default();
+    assert_eq!(
+        kdtree
+            .within_radius_with_distance(&[0, 0], 5, options)
+            .len(),
+        2
+    );
+    let options = WithinRadiusOptions {
+        inclusive: true,
+        ..options
+    };
+    assert_eq!(
+        kdtree
+            .within_radius_with_distance(&[0, 0], 5, options)
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn test_nearests_filtered() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    const NUM: usize = 5;
+    let predicate = |p: &[f64; 3]| p[0] + p[1] < 0.5;
+    for _ in 0..100 {
+        let query = gen3d();
+        let found = kdtree.nearests_filtered(&query, NUM, predicate);
+        assert_eq!(found.len(), NUM);
+        assert!(found.iter().all(|p| predicate(p.item)));
+        let count = kdtree
+            .iter()
+            .filter(|p| predicate(p))
+            .filter(|p| squared_distance(p, &query) <= found[NUM - 1].squared_distance)
+            .count();
+        assert_eq!(count, NUM);
+        assert_eq!(
+            kdtree.nearest_filtered(&query, predicate).unwrap().item,
+            found[0].item
+        );
+    }
+    assert!(kdtree.nearest_filtered(&[0.0; 3], |_| false).is_none());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_build() {
+    let mut gen3d = random3d_generator();
+    let points = vec(100000, |_| gen3d());
+    let kdtree = KdTree::par_build_by_ordered_float(points.clone());
+    assert_eq!(kdtree, KdTree::build_by_ordered_float(points.clone()));
+    let index_tree = KdIndexTree::par_build_by_ordered_float(&points);
+    for _ in 0..100 {
+        let query = gen3d();
+        assert_eq!(
+            &points[*index_tree.nearest(&query).unwrap().item],
+            kdtree.nearest(&query).unwrap().item
+        );
+    }
+}
+
+#[test]
+fn test_batch() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    let queries = vec(100, |_| gen3d());
+    let found = kdtree.nearest_batch(&queries);
+    let nearests = kdtree.nearests_batch(&queries, 5);
+    let within_radius = kdtree.within_radius_batch(&queries, 0.1);
+    for (i, query) in queries.iter().enumerate() {
+        assert_eq!(found[i], kdtree.nearest(query));
+        assert_eq!(nearests[i], kdtree.nearests(query, 5));
+        assert_eq!(within_radius[i], kdtree.within_radius(query, 0.1));
+    }
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(kdtree.par_nearest_batch(&queries), found);
+        assert_eq!(kdtree.par_nearests_batch(&queries, 5), nearests);
+        assert_eq!(kdtree.par_within_radius_batch(&queries, 0.1), within_radius);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let mut gen3d = random3d_generator();
+    let points = vec(1000, |_| gen3d().map(|x| (x * 1000.0) as i32));
+    let kdtree = KdTree::build(points.clone());
+    let json = serde_json::to_string(&kdtree).unwrap();
+    assert_eq!(
+        serde_json::from_str::<KdTree<[i32; 3]>>(&json).unwrap(),
+        kdtree
+    );
+    let json = serde_json::to_string(&KdTree::build_with_strategy(
+        points.clone(),
+        SplitStrategy::MaxSpread,
+    ))
+    .unwrap();
+    let deserialized = serde_json::from_str::<KdTree<[i32; 3]>>(&json).unwrap();
+    assert!(matches!(deserialized.split_axes(), Axes::PerNode(_)));
+    let json = format!(r#"{{"items":{}}}"#, serde_json::to_string(&points).unwrap());
+    assert!(serde_json::from_str::<KdTree<[i32; 3]>>(&json).is_err());
+    let json = serde_json::to_string(&kdtree)
+        .unwrap()
+        .replace(r#""axes":[]"#, r#""axes":[3]"#);
+    assert!(serde_json::from_str::<KdTree<[i32; 3]>>(&json).is_err());
+
+    let index_tree = KdIndexTree::build(&points);
+    let json = serde_json::to_string(&index_tree).unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let deserialized = KdIndexTree::deserialize_indices(&points, &mut deserializer).unwrap();
+    assert_eq!(deserialized, index_tree);
+    let indices: Vec<_> = (0..points.len()).collect();
+    let json = format!(
+        r#"{{"items":{}}}"#,
+        serde_json::to_string(&indices).unwrap()
+    );
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    assert!(KdIndexTree::deserialize_indices(&points, &mut deserializer).is_err());
+
+    let graph = kdtree.knn_graph(3);
+    let json = serde_json::to_string(&graph).unwrap();
+    assert_eq!(serde_json::from_str::<KnnGraph<i32>>(&json).unwrap(), graph);
+    let json = serde_json::to_string(&KnnGraph::<i32>::default()).unwrap();
+    assert!(serde_json::from_str::<KnnGraph<i32>>(&json)
+        .unwrap()
+        .is_empty());
+    for json in &[
+        r#"{"offsets":[],"neighbors":[],"squared_distances":[]}"#,
+        r#"{"offsets":[1,1],"neighbors":[0],"squared_distances":[0]}"#,
+        r#"{"offsets":[0,2,1],"neighbors":[1,0],"squared_distances":[0,0]}"#,
+        r#"{"offsets":[0,1,3],"neighbors":[1,0],"squared_distances":[0,0]}"#,
+        r#"{"offsets":[0,1,2],"neighbors":[1,0],"squared_distances":[0]}"#,
+        r#"{"offsets":[0,1,2],"neighbors":[1,2],"squared_distances":[0,0]}"#,
+    ] {
+        assert!(serde_json::from_str::<KnnGraph<i32>>(json).is_err());
+    }
+}
+
+#[test]
+#[should_panic(expected = "the dimension of a kd-tree must be positive")]
+fn test_is_kd_sorted_zero_dim() {
+    is_kd_sorted_by(&[1, 2], 0, |a: &i32, b: &i32, _| a.cmp(b));
+}
+
+#[test]
+fn test_try_build_by_ordered_float() {
+    let mut gen3d = random3d_generator();
+    let mut points = vec(100, |_| gen3d());
+    assert!(KdTree::try_build_by_ordered_float(points.clone()).is_ok());
+    points[42][2] = f64::NAN;
+    let err = KdIndexTree::try_build_by_ordered_float(&points).unwrap_err();
+    assert_eq!((err.index, err.axis), (42, 2));
+    let err = KdTree::try_build_by_ordered_float(points.clone()).unwrap_err();
+    assert_eq!((err.index, err.axis), (42, 2));
+    let items = err.into_inner();
+    assert_eq!(items.len(), points.len());
+    assert_eq!(items[..42], points[..42]);
+}
+
+#[test]
+fn test_from_sorted() {
+    let mut gen3d = random3d_generator();
+    let mut points = vec(1000, |_| gen3d());
+    assert!(KdSlice::from_sorted(&points).is_err());
+    KdSlice::sort_by_ordered_float(&mut points);
+    let kdslice = KdSlice::from_sorted(&points).unwrap();
+    let kdtree = KdTree::from_sorted_vec(points.clone()).unwrap();
+    for _ in 0..100 {
+        let query = gen3d();
+        assert_eq!(kdslice.nearest(&query), kdtree.nearest(&query));
+    }
+    let mid = points.len() / 2;
+    points.swap(0, mid);
+    let err = KdTree::from_sorted_vec(points.clone()).unwrap_err();
+    assert_eq!(err.into_inner(), points);
+}
+
+#[test]
+fn test_high_dimension() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut gen32d = || -> [f32; 32] {
+        let mut p = [0.0; 32];
+        for x in p.iter_mut() {
+            *x = rng.gen();
+        }
+        p
+    };
+    let kdtree = KdTree::build_by_ordered_float(vec(1000, |_| gen32d()));
+    let distance = |p1: &[f32; 32], p2: &[f32; 32]| -> f32 {
+        (0..32).map(|k| (p1[k] - p2[k]) * (p1[k] - p2[k])).sum()
+    };
+    for _ in 0..100 {
+        let query = gen32d();
+        let found = kdtree.nearest(&query).unwrap();
+        let expected = kdtree
+            .iter()
+            .min_by_key(|p| ordered_float::OrderedFloat(distance(p, &query)))
+            .unwrap();
+        assert_eq!(found.item, expected);
+    }
+
+    // typenum dimensions of user defined points interoperate with arrays.
+    struct Item([f64; 3]);
+    impl KdPoint for Item {
+        type Scalar = f64;
+        type Dim = typenum::U3;
+        fn at(&self, k: usize) -> f64 {
+            self.0[k]
+        }
+    }
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(1000, |_| Item(gen3d())));
+    for _ in 0..100 {
+        let query = gen3d();
+        let found = kdtree.nearest(&query).unwrap().item;
+        let expected = kdtree
+            .iter()
+            .min_by_key(|p| ordered_float::OrderedFloat(squared_distance(&p.0, &query)))
+            .unwrap();
+        assert_eq!(found.0, expected.0);
+        assert_eq!(kdtree.nearest(&Item(query)).unwrap().item.0, expected.0);
+    }
+    let kdtree: KdTree3<[f64; 3]> = KdTree::build_by_ordered_float(vec(100, |_| gen3d()));
+    assert_eq!(
+        kdtree.nearest(&Item([0.5; 3])).unwrap().item,
+        kdtree.nearest(&[0.5; 3]).unwrap().item
+    );
+}
+
+#[test]
+fn test_dyn_kdtree() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    const DIM: usize = 5;
+    let buffer: Vec<f64> = vec(1000 * (DIM + 1), |_| rng.gen());
+    let rows: Vec<Vec<f64>> = buffer
+        .chunks(DIM + 1)
+        .map(|row| row[..DIM].to_vec())
+        .collect();
+    let kdtree = DynKdTree::build_by_ordered_float(rows, DIM);
+    let flat_kdtree = DynKdTree::from_flat_by_ordered_float(&buffer, DIM, DIM + 1);
+    let distance = |p1: &[f64], p2: &[f64]| -> f64 {
+        (0..DIM).map(|k| (p1[k] - p2[k]) * (p1[k] - p2[k])).sum()
+    };
+    const NUM: usize = 5;
+    const RADIUS: f64 = 0.3;
+    for _ in 0..100 {
+        let query = vec(DIM, |_| rng.gen::<f64>());
+        let expected = kdtree
+            .iter()
+            .min_by_key(|p| ordered_float::OrderedFloat(distance(p, &query)))
+            .unwrap();
+        assert_eq!(kdtree.as_slice().nearest(&query).unwrap().item, expected);
+        assert_eq!(
+            flat_kdtree.as_slice().nearest(&query).unwrap().item,
+            &&expected[..]
+        );
+
+        let found = kdtree.as_slice().nearests(&query, NUM);
+        assert_eq!(found.len(), NUM);
+        let count = kdtree
+            .iter()
+            .filter(|p| distance(p, &query) <= found[NUM - 1].squared_distance)
+            .count();
+        assert_eq!(count, NUM);
+
+        let found = flat_kdtree.as_slice().within_radius(&query, RADIUS);
+        let count = kdtree
+            .iter()
+            .filter(|p| distance(p, &query) < RADIUS * RADIUS)
+            .count();
+        assert_eq!(found.len(), count);
+    }
+}
+
+#[test]
+fn test_bucket_tree() {
+    let mut gen3d = random3d_generator();
+    const NUM: usize = 5;
+    const RADIUS: f64 = 0.1;
+    let strategies = [
+        SplitStrategy::RoundRobin,
+        SplitStrategy::MaxSpread,
+        SplitStrategy::SlidingMidpoint,
+    ];
+    let configs = [1, 8, 32].iter().flat_map(|&bucket_size| {
+        strategies
+            .iter()
+            .map(move |&strategy| (bucket_size, strategy))
+    });
+    for (bucket_size, strategy) in configs {
+        // flat in z, like a LiDAR scan.
+        let points = vec(10000, |_| {
+            let [x, y, z] = gen3d();
+            [x, y, z * 0.01]
+        });
+        let kdtree = KdBucketTree::build_with_strategy(points, bucket_size, strategy);
+        for _ in 0..100 {
+            let query = gen3d();
+            let found = kdtree.nearest(&query).unwrap().item;
+            let expected = kdtree
+                .iter()
+                .min_by_key(|p| ordered_float::OrderedFloat(squared_distance(p, &query)))
+                .unwrap();
+            assert_eq!(found, expected);
+
+            let found = kdtree.nearests(&query, NUM);
+            assert_eq!(found.len(), NUM);
+            for i in 1..found.len() {
+                assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+            }
+            let count = kdtree
+                .iter()
+                .filter(|p| squared_distance(p, &query) <= found[NUM - 1].squared_distance)
+                .count();
+            assert_eq!(count, NUM);
+
+            let found = kdtree.within_radius(&query, RADIUS);
+            let count = kdtree
+                .iter()
+                .filter(|p| squared_distance(p, &query) < RADIUS * RADIUS)
+                .count();
+            assert_eq!(found.len(), count);
+
+            let mut p1 = gen3d();
+            let mut p2 = gen3d();
+            for k in 0..3 {
+                if p1[k] > p2[k] {
+                    std::mem::swap(&mut p1[k], &mut p2[k]);
+                }
+            }
+            let found = kdtree.within(&[p1, p2]);
+            let count = kdtree
+                .iter()
+                .filter(|p| (0..3).all(|k| p1[k] <= p[k] && p[k] <= p2[k]))
+                .count();
+            assert_eq!(found.len(), count);
+        }
+    }
+}
+
+#[test]
+fn test_split_tree() {
+    let mut gen3d = random3d_generator();
+    const NUM: usize = 5;
+    const RADIUS: f64 = 0.1;
+    for &strategy in &[SplitStrategy::RoundRobin, SplitStrategy::MaxSpread] {
+        // flat in z, like a LiDAR scan.
+        let points = vec(10000, |_| {
+            let [x, y, z] = gen3d();
+            [x, y, z * 0.01]
+        });
+        let kdindex = KdIndexTree::build_with_strategy_by_ordered_float(&points, strategy);
+        let kdtree = KdTree::build_with_strategy_by_ordered_float(points.clone(), strategy);
+        match (strategy, kdtree.split_axes()) {
+            (SplitStrategy::RoundRobin, Axes::RoundRobin) => {}
+            (SplitStrategy::MaxSpread, Axes::PerNode(axes)) => {
+                assert_eq!(axes.len(), kdtree.len());
+                assert_ne!(axes[kdtree.len() / 2], 2);
+            }
+            _ => panic!("unexpected split axes for {:?}", strategy),
+        }
+        for _ in 0..100 {
+            let query = gen3d();
+            let found = kdtree.nearest(&query).unwrap().item;
+            let expected = kdtree
+                .iter()
+                .min_by_key(|p| ordered_float::OrderedFloat(squared_distance(p, &query)))
+                .unwrap();
+            assert_eq!(found, expected);
+            let found = kdindex.nearest(&query).unwrap().item;
+            assert_eq!(&points[*found], expected);
+            assert_eq!(kdtree.nearest_iter(&query).next().unwrap().item, expected);
+
+            let found = kdtree.nearests(&query, NUM);
+            assert_eq!(found.len(), NUM);
+            for i in 1..found.len() {
+                assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+            }
+            let count = kdtree
+                .iter()
+                .filter(|p| squared_distance(p, &query) <= found[NUM - 1].squared_distance)
+                .count();
+            assert_eq!(count, NUM);
+            assert_eq!(kdindex.nearests(&query, NUM).len(), NUM);
+
+            let found = kdtree.within_radius(&query, RADIUS);
+            let count = kdtree
+                .iter()
+                .filter(|p| squared_distance(p, &query) < RADIUS * RADIUS)
+                .count();
+            assert_eq!(found.len(), count);
+            assert_eq!(kdindex.within_radius(&query, RADIUS).len(), count);
+            assert_eq!(kdtree.count_within_radius(&query, RADIUS), count);
+            assert_eq!(kdtree.any_within_radius(&query, RADIUS), count > 0);
+
+            let mut p1 = gen3d();
+            let mut p2 = gen3d();
+            for k in 0..3 {
+                if p1[k] > p2[k] {
+                    std::mem::swap(&mut p1[k], &mut p2[k]);
+                }
+            }
+            let found = kdtree.within(&[p1, p2]);
+            let count = kdtree
+                .iter()
+                .filter(|p| (0..3).all(|k| p1[k] <= p[k] && p[k] <= p2[k]))
+                .count();
+            assert_eq!(found.len(), count);
+            assert_eq!(kdindex.within(&[p1, p2]).len(), count);
+            assert_eq!(kdtree.count_within(&[p1, p2]), count);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "sliding midpoint")]
+fn test_split_tree_sliding_midpoint() {
+    KdTree::build_with_strategy_by_ordered_float(
+        vec![[1.0, 2.0], [2.0, 1.0]],
+        SplitStrategy::SlidingMidpoint,
+    );
+}
+
+#[test]
+fn test_coords() {
+    struct Item {
+        point: [f64; 3],
+        _payload: [u8; 256],
+    }
+    impl KdPoint for Item {
+        type Scalar = f64;
+        type Dim = Const<3>;
+        fn at(&self, k: usize) -> f64 {
+            self.point[k]
+        }
+    }
+    let mut gen3d = random3d_generator();
+    let items = vec(10000, |_| Item {
+        point: gen3d(),
+        _payload: [0; 256],
+    });
+    let kdtree = KdIndexTree::build_by_ordered_float(&items);
+    let cached = KdIndexTree::build_by_ordered_float(&items).with_coords();
+    assert_eq!(cached.indices().len(), items.len());
+    const NUM: usize = 5;
+    const RADIUS: f64 = 0.1;
+    for _ in 0..100 {
+        let query = gen3d();
+        assert_eq!(cached.nearest(&query), kdtree.nearest(&query));
+        assert_eq!(cached.nearests(&query, NUM), kdtree.nearests(&query, NUM));
+        let mut found = cached.within_radius(&query, RADIUS);
+        let mut expected = kdtree.within_radius(&query, RADIUS);
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+        let mut p1 = gen3d();
+        let mut p2 = gen3d();
+        for k in 0..3 {
+            if p1[k] > p2[k] {
+                std::mem::swap(&mut p1[k], &mut p2[k]);
+            }
+        }
+        let mut found = cached.within(&[p1, p2]);
+        let mut expected = kdtree.within(&[p1, p2]);
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+        assert_eq!(cached.count_within(&[p1, p2]), expected.len());
+        assert_eq!(cached.any_within(&[p1, p2]), !expected.is_empty());
+
+        assert_eq!(
+            cached.nearests_with_metric(&query, NUM, &Manhattan),
+            kdtree.nearests_with_metric(&query, NUM, &Manhattan)
+        );
+        assert_eq!(
+            cached.nearests_filtered(&query, NUM, |&i| i % 2 == 0),
+            kdtree.nearests_filtered(&query, NUM, |&i| i % 2 == 0)
+        );
+        assert_eq!(
+            cached.nearests_within_radius(&query, NUM, RADIUS),
+            kdtree.nearests_within_radius(&query, NUM, RADIUS)
+        );
+        let mut nearests = Vec::new();
+        cached.nearests_into(&query, NUM, &mut nearests);
+        assert_eq!(nearests, kdtree.nearests(&query, NUM));
+        let mut found = cached.within_distance_with_metric(&query, RADIUS, &Chebyshev);
+        let mut expected = kdtree.within_distance_with_metric(&query, RADIUS, &Chebyshev);
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+        let options = WithinRadiusOptions {
+            sorted: true,
+            inclusive: true,
+        };
+        let found = cached.within_radius_with_distance(&query, RADIUS, options);
+        let expected = kdtree.within_radius_with_distance(&query, RADIUS, options);
+        assert_eq!(found.len(), expected.len());
+        assert_eq!(cached.count_within_radius(&query, RADIUS), expected.len());
+        assert_eq!(
+            cached.any_within_radius(&query, RADIUS),
+            !expected.is_empty()
+        );
+    }
+}
+
+#[test]
+fn test_nearests_large_k() {
+    let mut gen3d = random3d_generator();
+    let kdtree = KdTree::build_by_ordered_float(vec(10000, |_| gen3d()));
+    const NUM: usize = 2000;
+    for _ in 0..10 {
+        let query = gen3d();
+        let found = kdtree.nearests(&query, NUM);
+        assert_eq!(found.len(), NUM);
+        for i in 1..found.len() {
+            assert!(found[i - 1].squared_distance <= found[i].squared_distance);
+        }
+        let count = kdtree
+            .iter()
+            .filter(|p| squared_distance(p, &query) <= found[NUM - 1].squared_distance)
+            .count();
+        assert_eq!(count, NUM);
+
+        let mut unsorted = kdtree.nearests_unsorted(&query, NUM);
+        assert_eq!(unsorted.len(), NUM);
+        unsorted.sort_by(|a, b| a.squared_distance.partial_cmp(&b.squared_distance).unwrap());
+        for (a, b) in found.iter().zip(&unsorted) {
+            assert_eq!(a.squared_distance, b.squared_distance);
+        }
+    }
+}
+
+#[test]
+fn test_query_into() {
+    let mut gen3d = random3d_generator();
+    let points = vec(10000, |_| gen3d());
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    let kdindex = KdIndexTree::build_by_ordered_float(&points);
+    const NUM: usize = 5;
+    const RADIUS: f64 = 0.1;
+    let mut nearests = Vec::new();
+    let mut nearest_indices = Vec::new();
+    let mut found = Vec::new();
+    let mut found_indices = Vec::new();
+    kdtree.nearests_into(&gen3d(), NUM, &mut nearests);
+    let capacity = nearests.capacity();
+    for _ in 0..100 {
+        let query = gen3d();
+        kdtree.nearests_into(&query, NUM, &mut nearests);
+        assert_eq!(nearests, kdtree.nearests(&query, NUM));
+        assert_eq!(nearests.capacity(), capacity);
+        kdindex.nearests_into(&query, NUM, &mut nearest_indices);
+        assert_eq!(nearest_indices, kdindex.nearests(&query, NUM));
+
+        kdtree.within_radius_into(&query, RADIUS, &mut found);
+        assert_eq!(found, kdtree.within_radius(&query, RADIUS));
+        kdindex.within_radius_into(&query, RADIUS, &mut found_indices);
+        assert_eq!(found_indices, kdindex.within_radius(&query, RADIUS));
+        let mut count = 0;
+        kdtree.for_each_within_radius(&query, RADIUS, |_| count += 1);
+        assert_eq!(count, found.len());
+        let mut count = 0;
+        kdindex.for_each_within_radius(&query, RADIUS, |_| count += 1);
+        assert_eq!(count, found.len());
+
+        let mut p1 = gen3d();
+        let mut p2 = gen3d();
+        for k in 0..3 {
+            if p1[k] > p2[k] {
+                std::mem::swap(&mut p1[k], &mut p2[k]);
+            }
+        }
+        kdtree.within_into(&[p1, p2], &mut found);
+        assert_eq!(found, kdtree.within(&[p1, p2]));
+        kdindex.within_into(&[p1, p2], &mut found_indices);
+        assert_eq!(found_indices, kdindex.within(&[p1, p2]));
+        let mut count = 0;
+        kdtree.for_each_within(&[p1, p2], |_| count += 1);
+        assert_eq!(count, found.len());
+        let mut count = 0;
+        kdindex.for_each_within(&[p1, p2], |_| count += 1);
+        assert_eq!(count, found.len());
+    }
+}
+
+#[test]
+fn test_count_within() {
+    let mut gen3d = random3d_generator();
+    let points = vec(10000, |_| gen3d());
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    let kdindex = KdIndexTree::build_by_ordered_float(&points);
+    for &radius in &[0.01, 0.1, 0.5] {
+        for _ in 0..100 {
+            let query = gen3d();
+            let count = kdtree.within_radius(&query, radius).len();
+            assert_eq!(kdtree.count_within_radius(&query, radius), count);
+            assert_eq!(kdindex.count_within_radius(&query, radius), count);
+            assert_eq!(kdtree.any_within_radius(&query, radius), count > 0);
+            assert_eq!(kdindex.any_within_radius(&query, radius), count > 0);
+
+            let mut p1 = gen3d();
+            let mut p2 = [0.0; 3];
+            for k in 0..3 {
+                p2[k] = p1[k] + radius;
+                p1[k] -= radius;
+            }
+            let count = kdtree.within(&[p1, p2]).len();
+            assert_eq!(kdtree.count_within(&[p1, p2]), count);
+            assert_eq!(kdindex.count_within(&[p1, p2]), count);
+            assert_eq!(kdtree.any_within(&[p1, p2]), count > 0);
+            assert_eq!(kdindex.any_within(&[p1, p2]), count > 0);
+        }
+    }
+}
+
+#[test]
+fn test_periodic() {
+    let mut gen3d = random3d_generator();
+    let points = vec(1000, |_| gen3d());
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    let kdindex = KdIndexTree::build_by_ordered_float(&points);
+    let periodic = Periodic::new(vec![1.0; 3]);
+    // `-1e-20 + 1.0` rounds to the period.
+    assert_eq!(periodic.wrap(0, -1e-20), 0.0);
+    assert!(periodic.wrap(0, -1e-10) < 1.0);
+    for _ in 0..100 {
+        let mut query = gen3d();
+        let distances = vec(points.len(), |i| {
+            periodic.squared_distance(|k| points[i][k], |k| query[k])
+        });
+        // queries outside the domain are wrapped.
+        query[0] -= 1.0;
+        query[2] += 2.0;
+
+        let mut sorted = distances.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let found = kdtree.nearest_periodic(&query, &periodic).unwrap();
+        assert!((found.squared_distance - sorted[0]).abs() < 1e-12);
+        let found = kdindex.nearest_periodic(&query, &periodic).unwrap();
+        assert!((distances[*found.item] - sorted[0]).abs() < 1e-12);
+
+        for &num in &[1, 10, 100] {
+            let found = kdtree.nearests_periodic(&query, num, &periodic);
+            assert_eq!(found.len(), num);
+            for (found, &expected) in found.iter().zip(&sorted) {
+                assert!((found.squared_distance - expected).abs() < 1e-12);
+            }
+            let found = kdindex.nearests_periodic(&query, num, &periodic);
+            for (found, &expected) in found.iter().zip(&sorted) {
+                assert!((distances[*found.item] - expected).abs() < 1e-12);
+            }
+        }
+
+        for &radius in &[0.1, 0.3, 0.6] {
+            let count = distances.iter().filter(|&&d| d < radius * radius).count();
+            let found = kdtree.within_radius_periodic(&query, radius, &periodic);
+            assert_eq!(found.len(), count);
+            let mut found = kdindex.within_radius_periodic(&query, radius, &periodic);
+            assert_eq!(found.len(), count);
+            found.sort();
+            found.dedup();
+            assert_eq!(found.len(), count);
+            assert!(found.iter().all(|&&i| distances[i] < radius * radius));
+        }
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "an item is outside the periodic domain")]
+fn test_periodic_item_outside_domain() {
+    let kdtree = KdTree::build_by_ordered_float(vec![[0.5, 0.5], [1.5, 0.5]]);
+    kdtree.nearests_periodic(&[0.5, 0.5], 2, &Periodic::new(vec![1.0, 1.0]));
+}
+
+#[test]
+fn test_geo() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut gen = || LatLon::new(rng.gen_range(-90.0, 90.0), rng.gen_range(-180.0, 180.0));
+    let points = vec(1000, |_| gen());
+    let kdtree = KdGeoTree::build(points.clone());
+    assert_eq!(kdtree.len(), points.len());
+    for _ in 0..100 {
+        let query = gen();
+        let mut distances = vec(points.len(), |i| points[i].distance_to(query));
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let found = kdtree.nearest(query).unwrap();
+        assert!((found.distance - distances[0]).abs() < 1e-3);
+        assert!((found.item.distance_to(query) - found.distance).abs() < 1e-3);
+        let found = kdtree.nearests(query, 10);
+        assert_eq!(found.len(), 10);
+        for (found, &expected) in found.iter().zip(&distances) {
+            assert!((found.distance - expected).abs() < 1e-3);
+        }
+        for &distance in &[100_000.0, 1_000_000.0, 10_000_000.0, 30_000_000.0] {
+            let found = kdtree.within_distance(query, distance);
+            let count = distances.iter().filter(|&&d| d < distance).count();
+            assert_eq!(found.len(), count);
+            assert!(found.iter().all(|found| found.distance < distance));
+        }
+    }
+}
+
+#[test]
+fn test_within_region() {
+    fn check<R: Region<f64>>(points: &[[f64; 3]], kdtree: &KdTree<[f64; 3]>, region: R) {
+        let count = points.iter().filter(|p| region.contains(&p[..])).count();
+        let found = kdtree.within_region(&region);
+        assert_eq!(found.len(), count);
+        assert!(found.iter().all(|p| region.contains(&p[..])));
+        let kdindex = KdIndexTree::build_by_ordered_float(points);
+        let mut found = kdindex.within_region(&region);
+        found.sort();
+        found.dedup();
+        assert_eq!(found.len(), count);
+    }
+    fn unit(v: [f64; 3]) -> [f64; 3] {
+        let norm = squared_distance(&v, &[0.0; 3]).sqrt();
+        [v[0] / norm, v[1] / norm, v[2] / norm]
+    }
+    fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+    fn scale(v: [f64; 3], s: f64) -> [f64; 3] {
+        [v[0] * s, v[1] * s, v[2] * s]
+    }
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut gen3d = random3d_generator();
+    let points = vec(10000, |_| gen3d());
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    for _ in 0..20 {
+        let center = gen3d();
+        let normal = unit([gen3d()[0] - 0.5, gen3d()[1] - 0.5, gen3d()[2] - 0.5]);
+        check(
+            &points,
+            &kdtree,
+            HalfSpace::from_point_normal(center, normal),
+        );
+
+        let planes = vec(rng.gen_range(1, 8), |_| {
+            let normal = unit([gen3d()[0] - 0.5, gen3d()[1] - 0.5, gen3d()[2] - 0.5]);
+            HalfSpace::from_point_normal(gen3d(), normal)
+        });
+        check(&points, &kdtree, ConvexPolytope::new(planes));
+
+        // an orthogonal frame.
+        let u = normal;
+        let v = unit(cross(u, gen3d()));
+        let w = cross(u, v);
+        let (a, b, c) = (
+            rng.gen_range(0.01, 0.5),
+            rng.gen_range(0.01, 0.5),
+            rng.gen_range(0.01, 0.5),
+        );
+        check(
+            &points,
+            &kdtree,
+            Ellipsoid::new(center, [scale(u, a), scale(v, b), scale(w, c)]),
+        );
+        check(&points, &kdtree, Ellipsoid::axis_aligned(center, [a, b, c]));
+        check(
+            &points,
+            &kdtree,
+            OrientedBox::new(center, [scale(u, a), scale(v, b), scale(w, c)]),
+        );
+        check(&points, &kdtree, Capsule::new(center, gen3d(), a));
+        check(&points, &kdtree, Capsule::new(center, center, a));
+    }
+}
+
+#[test]
+#[should_panic(expected = "the dimension of the region differs from the kd-tree")]
+fn test_within_region_dim_mismatch() {
+    let kdtree = KdTree::build_by_ordered_float(vec![[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]]);
+    kdtree.within_region(&HalfSpace::new([1.0, 1.0], 1.0));
+}
+
+#[test]
+fn test_segment_queries() {
+    fn segment_distance(p: &[f64; 3], a: &[f64; 3], b: &[f64; 3], t_max: f64) -> (f64, f64) {
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ap = [p[0] - a[0], p[1] - a[1], p[2] - a[2]];
+        let length = squared_distance(&ab, &[0.0; 3]);
+        let dot = ab[0] * ap[0] + ab[1] * ap[1] + ab[2] * ap[2];
+        let t = if length > 0.0 {
+            (dot / length).max(0.0).min(t_max)
+        } else {
+            0.0
+        };
+        let nearest = [a[0] + t * ab[0], a[1] + t * ab[1], a[2] + t * ab[2]];
+        (t, squared_distance(p, &nearest))
+    }
+    let mut gen3d = random3d_generator();
+    let points = vec(3000, |_| gen3d());
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    let kdindex = KdIndexTree::build_by_ordered_float(&points);
+    for _ in 0..100 {
+        let (a, b) = (gen3d(), gen3d());
+        let mut distances = vec(points.len(), |i| {
+            segment_distance(&points[i], &a, &b, 1.0).1
+        });
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let found = kdtree.nearest_to_segment(&a, &b).unwrap();
+        assert_eq!(found.squared_distance, distances[0]);
+        let found = kdindex.nearest_to_segment(&a, &b).unwrap();
+        assert_eq!(found.squared_distance, distances[0]);
+        let found = kdtree.nearests_to_segment(&a, &b, 10);
+        assert_eq!(found.len(), 10);
+        for (found, &expected) in found.iter().zip(&distances) {
+            assert_eq!(found.squared_distance, expected);
+        }
+
+        let vertices = vec(4, |_| gen3d());
+        for &radius in &[0.01, 0.05, 0.2] {
+            let count = points
+                .iter()
+                .filter(|p| {
+                    vertices.windows(2).any(|pair| {
+                        segment_distance(p, &pair[0], &pair[1], 1.0).1 < radius * radius
+                    })
+                })
+                .count();
+            assert_eq!(
+                kdtree.within_distance_of_polyline(&vertices, radius).len(),
+                count
+            );
+            assert_eq!(
+                kdindex.within_distance_of_polyline(&vertices, radius).len(),
+                count
+            );
+        }
+
+        let origin = gen3d();
+        let direction = [gen3d()[0] - 0.5, gen3d()[1] - 0.5, gen3d()[2] - 0.5];
+        let direction = [direction[0], direction[1], 0.0];
+        for &direction in &[direction, [0.0, 0.0, 1.0]] {
+            let tolerance = 0.02;
+            let expected = points
+                .iter()
+                .map(|p| {
+                    segment_distance(
+                        p,
+                        &origin,
+                        &[
+                            origin[0] + direction[0],
+                            origin[1] + direction[1],
+                            origin[2] + direction[2],
+                        ],
+                        f64::INFINITY,
+                    )
+                })
+                .filter(|&(_, d)| d <= tolerance * tolerance)
+                .map(|(t, _)| t)
+                .fold(None, |min: Option<f64>, t| {
+                    Some(min.map_or(t, |min| min.min(t)))
+                });
+            let same = |found: Option<f64>| match (found, expected) {
+                (Some(found), Some(expected)) => (found - expected).abs() < 1e-12,
+                (found, expected) => found == expected,
+            };
+            assert!(same(
+                kdtree
+                    .first_along_ray(&origin, &direction, tolerance)
+                    .map(|hit| hit.t)
+            ));
+            assert!(same(
+                kdindex
+                    .first_along_ray(&origin, &direction, tolerance)
+                    .map(|hit| hit.t)
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_knn_graph() {
+    let mut gen3d = random3d_generator();
+    let mut points = vec(1000, |_| gen3d());
+    points.extend_from_within(..100);
+    let kdtree = KdTree::build_by_ordered_float(points.clone());
+    let kdindex = KdIndexTree::build_by_ordered_float(&points);
+    let k = 5;
+    assert!(KnnGraph::<f64>::default().is_empty());
+    let graph = kdtree.knn_graph(k);
+    let index_graph = kdindex.knn_graph(k);
+    assert_eq!(graph.len(), points.len());
+    assert_eq!(graph.num_edges(), points.len() * k);
+    for i in 0..points.len() {
+        let mut distances = vec(points.len(), |j| squared_distance(&kdtree[i], &kdtree[j]));
+        distances.remove(i);
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(!graph.neighbors(i).contains(&i));
+        assert_eq!(graph.squared_distances(i), &distances[..k]);
+        for (&j, &d) in graph.neighbors(i).iter().zip(graph.squared_distances(i)) {
+            assert_eq!(squared_distance(&kdtree[i], &kdtree[j]), d);
+        }
+        assert!(!index_graph.neighbors(i).contains(&i));
+        let mut distances = vec(points.len(), |j| squared_distance(&points[i], &points[j]));
+        distances.remove(i);
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(index_graph.squared_distances(i), &distances[..k]);
+    }
+
+    let options = |symmetrize| KnnGraphOptions { symmetrize };
+    let mutual = kdtree.knn_graph_with_options(k, options(Symmetrize::Mutual));
+    let union = kdtree.knn_graph_with_options(k, options(Symmetrize::Union));
+    for i in 0..points.len() {
+        for &j in mutual.neighbors(i) {
+            assert!(graph.neighbors(i).contains(&j) && graph.neighbors(j).contains(&i));
+            assert!(mutual.neighbors(j).contains(&i));
+        }
+        for &j in union.neighbors(i) {
+            assert!(graph.neighbors(i).contains(&j) || graph.neighbors(j).contains(&i));
+            assert!(union.neighbors(j).contains(&i));
+        }
+        assert!(graph
+            .neighbors(i)
+            .iter()
+            .all(|j| union.neighbors(i).contains(j)));
+        assert!(union.squared_distances(i).windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(kdtree.par_knn_graph(k), graph);
+        assert_eq!(kdindex.par_knn_graph(k), index_graph);
+        assert_eq!(
+            kdtree.par_knn_graph_with_options(k, options(Symmetrize::Union)),
+            union
+        );
+    }
+}
+
 fn squared_distance<T: num_traits::Num + Copy>(p1: &[T; 3], p2: &[T; 3]) -> T {
     let dx = p1[0] - p2[0];
     let dy = p1[1] - p2[1];
@@ -90,5 +1233,6 @@ fn vec<T>(count: usize, mut f: impl FnMut(usize) -> T) -> Vec<T> {
     let mut items = Vec::with_capacity(count);
     for i in 0..count {
         items.push(f(i));
-    }    items
+    }
+    items
 }
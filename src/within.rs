@@ -1,47 +1,256 @@
+use crate::coords::CoordAt;
+use crate::region::{Overlap, Region};
+use crate::sort::Axes;
 use std::cmp::Ordering;
 
 pub fn kd_within_by_cmp<'a, T>(
     kdtree: &'a [T],
     dim: usize,
-    compare: impl Fn(&T, usize) -> Ordering + Copy,
+    axes: Axes<'_>,
+    compare: impl CoordAt<T, Ordering>,
 ) -> Vec<&'a T> {
+    let mut results = Vec::new();
+    kd_for_each_within_by_cmp(kdtree, dim, axes, compare, |item, _| results.push(item));
+    results
+}
+
+/// Calls `f` for each item in the region given by `compare` and its position in `kdtree`, instead of collecting them.
+/// The nodes are split along `axes`.
+pub fn kd_for_each_within_by_cmp<'a, T>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    compare: impl CoordAt<T, Ordering>,
+    mut f: impl FnMut(&'a T, usize),
+) {
+    #[allow(clippy::too_many_arguments)]
     fn recurse<'a, T>(
-        results: &mut Vec<&'a T>,
+        f: &mut impl FnMut(&'a T, usize),
         kdtree: &'a [T],
+        offset: usize,
         axis: usize,
         dim: usize,
-        compare: impl Fn(&T, usize) -> Ordering + Copy,
+        axes: Axes<'_>,
+        compare: impl CoordAt<T, Ordering>,
     ) {
-        let axis = axis % dim;
-        let (lower, item, upper) = {
-            let mid = kdtree.len() / 2;
-            (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..])
-        };
-        match compare(item, axis) {
+        let mid = kdtree.len() / 2;
+        let axis = axes.get(offset + mid, axis % dim);
+        let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+        let position = offset + mid;
+        let upper_offset = position + 1;
+        match compare.at(position, item, axis) {
             Ordering::Equal => {
-                if (1..dim).all(|k| compare(item, (axis + k) % dim) == Ordering::Equal) {
-                    results.push(item);
+                if (1..dim).all(|k| compare.at(position, item, (axis + k) % dim) == Ordering::Equal)
+                {
+                    f(item, position);
                 }
                 if !lower.is_empty() {
-                    recurse(results, lower, axis + 1, dim, compare);
+                    recurse(f, lower, offset, axis + 1, dim, axes, compare);
                 }
                 if !upper.is_empty() {
-                    recurse(results, upper, axis + 1, dim, compare);
+                    recurse(f, upper, upper_offset, axis + 1, dim, axes, compare);
                 }
             }
             Ordering::Less => {
                 if !upper.is_empty() {
-                    recurse(results, upper, axis + 1, dim, compare);
+                    recurse(f, upper, upper_offset, axis + 1, dim, axes, compare);
                 }
             }
             Ordering::Greater => {
                 if !lower.is_empty() {
-                    recurse(results, lower, axis + 1, dim, compare);
+                    recurse(f, lower, offset, axis + 1, dim, axes, compare);
                 }
             }
         }
     }
-    let mut results = Vec::new();
-    recurse(&mut results, kdtree, 0, dim, compare);
-    results
+    if !kdtree.is_empty() {
+        recurse(&mut f, kdtree, 0, 0, dim, axes, compare);
+    }
+}
+
+/// Returns whether any item in the region given by `compare` satisfies `filter`, stopping at the first one.
+/// `filter` is given the item and its position in `kdtree`.
+/// The nodes are split along `axes`.
+pub fn kd_any_within_by_cmp<T>(
+    kdtree: &[T],
+    dim: usize,
+    axes: Axes<'_>,
+    compare: impl CoordAt<T, Ordering>,
+    filter: impl Fn(&T, usize) -> bool + Copy,
+) -> bool {
+    #[allow(clippy::too_many_arguments)]
+    fn recurse<T>(
+        kdtree: &[T],
+        offset: usize,
+        axis: usize,
+        dim: usize,
+        axes: Axes<'_>,
+        compare: impl CoordAt<T, Ordering>,
+        filter: impl Fn(&T, usize) -> bool + Copy,
+    ) -> bool {
+        let mid = kdtree.len() / 2;
+        let position = offset + mid;
+        let axis = axes.get(position, axis % dim);
+        let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+        let ordering = compare.at(position, item, axis);
+        (ordering == Ordering::Equal
+            && (1..dim).all(|k| compare.at(position, item, (axis + k) % dim) == Ordering::Equal)
+            && filter(item, position))
+            || (ordering != Ordering::Less
+                && !lower.is_empty()
+                && recurse(lower, offset, axis + 1, dim, axes, compare, filter))
+            || (ordering != Ordering::Greater
+                && !upper.is_empty()
+                && recurse(upper, position + 1, axis + 1, dim, axes, compare, filter))
+    }
+    !kdtree.is_empty() && recurse(kdtree, 0, 0, dim, axes, compare, filter)
+}
+
+/// Counts the items in the region given by `compare` which satisfy `filter`.
+/// `filter` is given the item and its position in `kdtree`.
+///
+/// `cell_inside` is given the bounds of the cell of a subtree, `[min[0], .., min[dim - 1], max[0], .., max[dim - 1]]`,
+/// where each bound is the item splitting along the axis and its position, or `None` if unbounded.
+/// If it returns `true`, all the items of the subtree are counted without visiting them.
+/// The nodes are split along `axes`.
+pub fn kd_count_within_by_cmp<'a, T>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    compare: impl CoordAt<T, Ordering>,
+    filter: impl Fn(&T, usize) -> bool + Copy,
+    cell_inside: impl Fn(&[Option<(&'a T, usize)>]) -> bool + Copy,
+) -> usize {
+    struct Context<'a, 'x, T, C, F, I> {
+        bounds: Vec<Option<(&'a T, usize)>>,
+        dim: usize,
+        axes: Axes<'x>,
+        compare: C,
+        filter: F,
+        cell_inside: I,
+    }
+    impl<'a, T, C, F, I> Context<'a, '_, T, C, F, I>
+    where
+        C: CoordAt<T, Ordering>,
+        F: Fn(&T, usize) -> bool,
+        I: Fn(&[Option<(&'a T, usize)>]) -> bool,
+    {
+        fn recurse(&mut self, kdtree: &'a [T], offset: usize, axis: usize) -> usize {
+            if (self.cell_inside)(&self.bounds) {
+                return kdtree.len();
+            }
+            let dim = self.dim;
+            let mid = kdtree.len() / 2;
+            let position = offset + mid;
+            let axis = self.axes.get(position, axis);
+            let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+            let compare = self.compare;
+            let ordering = compare.at(position, item, axis);
+            let mut count = 0;
+            if ordering == Ordering::Equal
+                && (1..dim).all(|k| compare.at(position, item, (axis + k) % dim) == Ordering::Equal)
+                && (self.filter)(item, position)
+            {
+                count += 1;
+            }
+            let next_axis = (axis + 1) % dim;
+            if ordering != Ordering::Less && !lower.is_empty() {
+                let max = self.bounds[dim + axis].replace((item, position));
+                count += self.recurse(lower, offset, next_axis);
+                self.bounds[dim + axis] = max;
+            }
+            if ordering != Ordering::Greater && !upper.is_empty() {
+                let min = self.bounds[axis].replace((item, position));
+                count += self.recurse(upper, position + 1, next_axis);
+                self.bounds[axis] = min;
+            }
+            count
+        }
+    }
+    if kdtree.is_empty() {
+        return 0;
+    }
+    Context {
+        bounds: vec![None; 2 * dim],
+        dim,
+        axes,
+        compare,
+        filter,
+        cell_inside,
+    }
+    .recurse(kdtree, 0, 0)
+}
+
+/// Calls `f` for each item in `region`, pruning the cells which `region` classifies as outside,
+/// and taking every item of the cells classified as inside.
+/// The nodes are split along `axes`.
+pub fn kd_for_each_within_region_by<'a, T, Scalar: Copy, R: Region<Scalar> + ?Sized>(
+    kdtree: &'a [T],
+    dim: usize,
+    axes: Axes<'_>,
+    region: &R,
+    coord: impl Fn(&T, usize) -> Scalar + Copy,
+    f: impl FnMut(&'a T),
+) {
+    struct Context<'r, 'x, Scalar, R: ?Sized, C, F> {
+        bounds: Vec<Option<Scalar>>,
+        point: Vec<Scalar>,
+        dim: usize,
+        axes: Axes<'x>,
+        region: &'r R,
+        coord: C,
+        f: F,
+    }
+    impl<Scalar: Copy, R: Region<Scalar> + ?Sized, C, F> Context<'_, '_, Scalar, R, C, F> {
+        fn recurse<'a, T>(&mut self, kdtree: &'a [T], offset: usize, axis: usize)
+        where
+            C: Fn(&T, usize) -> Scalar,
+            F: FnMut(&'a T),
+        {
+            let dim = self.dim;
+            match self
+                .region
+                .classify(&self.bounds[..dim], &self.bounds[dim..])
+            {
+                Overlap::Outside => return,
+                Overlap::Inside => return kdtree.iter().for_each(&mut self.f),
+                Overlap::Partial => {}
+            }
+            let mid = kdtree.len() / 2;
+            let position = offset + mid;
+            let axis = self.axes.get(position, axis);
+            let (lower, item, upper) = (&kdtree[..mid], &kdtree[mid], &kdtree[mid + 1..]);
+            self.point.clear();
+            let coord = &self.coord;
+            self.point.extend((0..dim).map(|k| coord(item, k)));
+            if self.region.contains(&self.point) {
+                (self.f)(item);
+            }
+            let split = self.point[axis];
+            let next_axis = (axis + 1) % dim;
+            if !lower.is_empty() {
+                let max = self.bounds[dim + axis].replace(split);
+                self.recurse(lower, offset, next_axis);
+                self.bounds[dim + axis] = max;
+            }
+            if !upper.is_empty() {
+                let min = self.bounds[axis].replace(split);
+                self.recurse(upper, position + 1, next_axis);
+                self.bounds[axis] = min;
+            }
+        }
+    }
+    if kdtree.is_empty() {
+        return;
+    }
+    Context {
+        bounds: vec![None; 2 * dim],
+        point: Vec::with_capacity(dim),
+        dim,
+        axes,
+        region,
+        coord,
+        f,
+    }
+    .recurse(kdtree, 0, 0)
 }